@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use crate::{Clock, StdClock, Stopwatch, TimeSpan, Transition};
+
+/// A stopwatch which only keeps its total time, without storing time spans.
+///
+/// It never allocates, which makes it suitable to time hot loops. It has the
+/// same methods as `Stopwatch` and converts to and from it.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut a = Accumulator::default();
+/// for _ in 0..1000 {
+///     a.start(); // Starts the accumulator, or creates a new split.
+/// }
+/// a.stop(); // Stops the accumulator.
+/// assert_eq!(a.count(), 1000);
+/// println!("{:?}", a.elapsed()); // Prints the total time.
+/// let s: Stopwatch = a.into(); // Converts to a stopwatch keeping the total time.
+/// ```
+#[derive(Clone, Debug)]
+pub struct Accumulator<C: Clock = StdClock> {
+    started: Option<C::Instant>,
+    total: Duration,
+    count: usize,
+    clock: C,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator::with_clock(StdClock)
+    }
+}
+
+impl<C: Clock> Accumulator<C> {
+    /// Creates a stopped accumulator which measures time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        Accumulator {
+            started: None,
+            total: Duration::ZERO,
+            count: 0,
+            clock,
+        }
+    }
+
+    /// Returns the clock used by this accumulator.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Starts the accumulator.
+    ///
+    /// If it is already started, it will create a new split. Returns the
+    /// duration of the time span which was stopped, if any.
+    pub fn start(&mut self) -> Option<Duration> {
+        let ret = self.stop();
+        self.started = Some(self.clock.now());
+        self.count += 1;
+        ret
+    }
+
+    /// Stops the accumulator without resetting it. Returns the duration of
+    /// the time span which was stopped, if any.
+    pub fn stop(&mut self) -> Option<Duration> {
+        let started = self.started.take()?;
+        let elapsed = self.clock.duration_between(started, self.clock.now());
+        self.total = self.total.saturating_add(elapsed);
+        Some(elapsed)
+    }
+
+    /// Starts the accumulator if it is stopped, otherwise does nothing.
+    ///
+    /// See `Stopwatch::resume`.
+    pub fn resume(&mut self) -> Transition {
+        if self.is_running() {
+            Transition::Unchanged
+        } else {
+            self.start();
+            Transition::Started
+        }
+    }
+
+    /// Stops the accumulator if it is running, otherwise does nothing.
+    pub fn pause(&mut self) -> Transition {
+        match self.stop() {
+            Some(_) => Transition::Stopped,
+            None => Transition::Unchanged,
+        }
+    }
+
+    /// Stops the accumulator if it is running, otherwise starts it.
+    pub fn toggle(&mut self) -> Transition {
+        match self.stop() {
+            Some(_) => Transition::Stopped,
+            None => {
+                self.start();
+                Transition::Started
+            }
+        }
+    }
+
+    /// Creates a new split if the accumulator is running, otherwise does
+    /// nothing.
+    pub fn split(&mut self) -> Transition {
+        if self.is_running() {
+            self.start();
+            Transition::Split
+        } else {
+            Transition::Unchanged
+        }
+    }
+
+    /// Returns whether the accumulator is running.
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Returns the total elapsed time accumulated inside of this accumulator.
+    pub fn elapsed(&self) -> Duration {
+        match self.started {
+            Some(started) => self
+                .total
+                .saturating_add(self.clock.duration_between(started, self.clock.now())),
+            None => self.total,
+        }
+    }
+
+    /// Returns the number of time spans started, including the running one.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Stops the accumulator and clears its total time.
+    pub fn reset(&mut self) {
+        self.started = None;
+        self.total = Duration::ZERO;
+        self.count = 0;
+    }
+}
+
+/// Keeps the total time and number of time spans of the stopwatch.
+impl<C: Clock> From<Stopwatch<C>> for Accumulator<C> {
+    fn from(stopwatch: Stopwatch<C>) -> Self {
+        let started = stopwatch
+            .spans()
+            .last()
+            .filter(|span| span.stop.is_none())
+            .map(|span| span.start);
+        let total = stopwatch
+            .iter()
+            .filter(|span| span.stop.is_some())
+            .fold(stopwatch.evicted_elapsed(), |total, span| {
+                total.saturating_add(span.elapsed_with(stopwatch.clock()))
+            });
+        Accumulator {
+            started,
+            total,
+            count: stopwatch.evicted_count() + stopwatch.spans().len(),
+            clock: stopwatch.clock,
+        }
+    }
+}
+
+/// Creates a stopwatch whose completed time spans count as evicted ones, see
+/// `Stopwatch::set_history_limit`. A running time span is kept.
+impl<C: Clock> From<Accumulator<C>> for Stopwatch<C> {
+    fn from(accumulator: Accumulator<C>) -> Self {
+        let running: Vec<_> = accumulator
+            .started
+            .map(|start| TimeSpan {
+                start,
+                stop: None,
+                label: None,
+            })
+            .into_iter()
+            .collect();
+        let evicted_count = accumulator.count - running.len();
+        Stopwatch::from_parts(accumulator.clock, running, evicted_count, accumulator.total)
+            .expect("a single running time span is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn accumulates() {
+        let clock = SharedManualClock::default();
+        let mut a = Accumulator::with_clock(clock.clone());
+        assert_eq!(a.stop(), None);
+        assert_eq!(a.resume(), Transition::Started);
+        clock.advance(ms(10));
+        assert_eq!(a.start(), Some(ms(10)));
+        clock.advance(ms(5));
+        assert_eq!(a.elapsed(), ms(15));
+        assert_eq!(a.toggle(), Transition::Stopped);
+        clock.advance(ms(100));
+        assert_eq!(a.split(), Transition::Unchanged);
+        assert_eq!(a.elapsed(), ms(15));
+        assert_eq!(a.count(), 2);
+        a.reset();
+        assert_eq!(a.elapsed(), Duration::ZERO);
+        assert_eq!(a.count(), 0);
+    }
+
+    #[test]
+    fn converts() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start();
+        clock.advance(ms(10));
+        sw.start();
+        clock.advance(ms(10));
+        sw.start();
+        clock.advance(ms(5));
+
+        let mut a = Accumulator::from(sw);
+        assert!(a.is_running());
+        assert_eq!(a.count(), 3);
+        assert_eq!(a.elapsed(), ms(25));
+        clock.advance(ms(5));
+        a.start();
+
+        let sw = Stopwatch::from(a);
+        assert!(sw.is_running());
+        assert_eq!(sw.spans().len(), 1);
+        assert_eq!(sw.evicted_count(), 3);
+        clock.advance(ms(10));
+        assert_eq!(sw.elapsed(), ms(40));
+        assert_eq!(Accumulator::from(sw).count(), 4);
+    }
+}
@@ -0,0 +1,269 @@
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::duration_from_nanos;
+#[cfg(feature = "std")]
+use crate::to_nanos;
+
+/// A source of time used by a stopwatch to measure its time spans.
+///
+/// The default clock is `StdClock`, which uses `std::time::Instant`. Implement
+/// this trait to plug in deterministic clocks for tests or custom time sources.
+pub trait Clock {
+    /// The point in time produced by this clock.
+    type Instant: Copy + Ord + fmt::Debug;
+
+    /// Returns the current instant.
+    fn now(&self) -> Self::Instant;
+
+    /// Returns the amount of time elapsed from `earlier` to `later`.
+    ///
+    /// Returns a zero duration if `later` precedes `earlier`.
+    fn duration_between(&self, earlier: Self::Instant, later: Self::Instant) -> Duration;
+
+    /// Returns the amount of time elapsed from `earlier` to `later`, or `None`
+    /// if `later` precedes `earlier`, which means the clock went backwards.
+    fn checked_duration_between(
+        &self,
+        earlier: Self::Instant,
+        later: Self::Instant,
+    ) -> Option<Duration> {
+        if later < earlier {
+            None
+        } else {
+            Some(self.duration_between(earlier, later))
+        }
+    }
+}
+
+/// A clock backed by `std::time::Instant`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    type Instant = Instant;
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn duration_between(&self, earlier: Instant, later: Instant) -> Duration {
+        later.saturating_duration_since(earlier)
+    }
+
+    fn checked_duration_between(&self, earlier: Instant, later: Instant) -> Option<Duration> {
+        later.checked_duration_since(earlier)
+    }
+}
+
+/// Allows a clock to be borrowed by a stopwatch instead of owned.
+impl<C: Clock + ?Sized> Clock for &C {
+    type Instant = C::Instant;
+
+    fn now(&self) -> Self::Instant {
+        (**self).now()
+    }
+
+    fn duration_between(&self, earlier: Self::Instant, later: Self::Instant) -> Duration {
+        (**self).duration_between(earlier, later)
+    }
+
+    fn checked_duration_between(
+        &self,
+        earlier: Self::Instant,
+        later: Self::Instant,
+    ) -> Option<Duration> {
+        (**self).checked_duration_between(earlier, later)
+    }
+}
+
+/// Allows a clock to be shared between several stopwatches.
+#[cfg(feature = "std")]
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    type Instant = C::Instant;
+
+    fn now(&self) -> Self::Instant {
+        (**self).now()
+    }
+
+    fn duration_between(&self, earlier: Self::Instant, later: Self::Instant) -> Duration {
+        (**self).duration_between(earlier, later)
+    }
+
+    fn checked_duration_between(
+        &self,
+        earlier: Self::Instant,
+        later: Self::Instant,
+    ) -> Option<Duration> {
+        (**self).checked_duration_between(earlier, later)
+    }
+}
+
+/// A clock whose time only advances when `advance` is called.
+///
+/// Its instants are the time elapsed since the clock was created. This is
+/// mostly useful to test timing code deterministically.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use std::time::Duration;
+///
+/// let clock = SharedManualClock::default();
+/// let mut s = Stopwatch::with_clock(clock.clone());
+/// s.start();
+/// clock.advance(Duration::from_millis(50));
+/// s.stop();
+/// assert_eq!(s.elapsed(), Duration::from_millis(50));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Default, Debug)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+}
+
+/// A `ManualClock` which can be shared between a test and the stopwatches it
+/// drives.
+#[cfg(feature = "std")]
+pub type SharedManualClock = Arc<ManualClock>;
+
+#[cfg(feature = "std")]
+impl ManualClock {
+    /// Creates a clock at time zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the time of this clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = to_nanos(duration);
+        self.nanos
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |now| {
+                Some(now.saturating_add(nanos))
+            })
+            .unwrap();
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for ManualClock {
+    type Instant = Duration;
+
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+
+    fn duration_between(&self, earlier: Duration, later: Duration) -> Duration {
+        later.saturating_sub(earlier)
+    }
+}
+
+/// A clock reading a monotonic tick counter, such as a hardware timer.
+///
+/// This is the usual clock of a `FixedStopwatch` in `no_std` environments.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use core::sync::atomic::{AtomicU64, Ordering};
+/// use core::time::Duration;
+///
+/// static TICKS: AtomicU64 = AtomicU64::new(0);
+///
+/// let clock = TickClock::new(|| TICKS.load(Ordering::SeqCst), 1_000);
+/// let mut s = FixedStopwatch::<_, 4>::with_clock(clock);
+/// s.start();
+/// TICKS.store(1_500, Ordering::SeqCst);
+/// s.stop();
+/// assert_eq!(s.elapsed(), Duration::from_millis(1_500));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TickClock<F> {
+    ticks: F,
+    ticks_per_second: u64,
+}
+
+impl<F: Fn() -> u64> TickClock<F> {
+    /// Creates a clock reading the current tick count with `ticks`, which
+    /// increases `ticks_per_second` times per second.
+    ///
+    /// # Panics
+    /// Panics if `ticks_per_second` is zero.
+    pub fn new(ticks: F, ticks_per_second: u64) -> Self {
+        assert!(ticks_per_second > 0, "ticks_per_second must not be zero");
+        TickClock {
+            ticks,
+            ticks_per_second,
+        }
+    }
+}
+
+impl<F: Fn() -> u64> Clock for TickClock<F> {
+    type Instant = u64;
+
+    fn now(&self) -> u64 {
+        (self.ticks)()
+    }
+
+    fn duration_between(&self, earlier: u64, later: u64) -> Duration {
+        let ticks = later.saturating_sub(earlier) as u128;
+        duration_from_nanos(ticks * 1_000_000_000 / self.ticks_per_second as u128)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn manual_clock_only_advances_when_told() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now(), Duration::ZERO);
+        clock.advance(Duration::from_millis(5));
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(clock.now(), Duration::from_millis(10));
+        clock.advance(Duration::MAX);
+        assert_eq!(clock.now(), Duration::from_nanos(u64::MAX));
+    }
+
+    #[test]
+    fn tick_clock() {
+        let clock = TickClock::new(|| 0, 3);
+        assert_eq!(clock.duration_between(3, 9), Duration::from_secs(2));
+        assert_eq!(
+            clock.duration_between(0, 1),
+            Duration::from_nanos(333_333_333)
+        );
+        assert_eq!(clock.duration_between(9, 3), Duration::ZERO);
+        assert_eq!(clock.checked_duration_between(9, 3), None);
+    }
+
+    #[test]
+    fn manual_clock_splits_and_pauses() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start();
+        clock.advance(Duration::from_millis(10));
+        sw.start();
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(sw.elapsed(), Duration::from_millis(30));
+        sw.stop();
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(sw.elapsed(), Duration::from_millis(30));
+        assert_eq!(
+            sw.spans()[0].elapsed_with(sw.clock()),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            sw.spans()[1].elapsed_with(sw.clock()),
+            Duration::from_millis(20)
+        );
+    }
+}
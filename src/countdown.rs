@@ -0,0 +1,180 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Clock, StdClock, Stopwatch, TimeSpan, Transition};
+
+/// A timer counting down from a target duration, built on a `Stopwatch`.
+///
+/// Stopping the countdown pauses it and starting it again resumes it, in the
+/// same way as the stopwatch it wraps.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use std::time::Duration;
+///
+/// let mut c = Countdown::new(Duration::from_secs(10));
+/// c.start(); // Starts counting down.
+/// c.stop(); // Pauses the countdown.
+/// println!("{:?} left", c.remaining());
+/// assert!(!c.is_expired());
+/// c.reset_with(Duration::from_secs(5)); // Resets the countdown with a new target.
+/// println!("{}", c); // Prints the remaining time.
+/// ```
+#[derive(Clone, Debug)]
+pub struct Countdown<C: Clock = StdClock> {
+    stopwatch: Stopwatch<C>,
+    target: Duration,
+}
+
+impl Countdown {
+    /// Creates a stopped countdown from `target`.
+    pub fn new(target: Duration) -> Self {
+        Countdown::with_clock(target, StdClock)
+    }
+}
+
+/// Prints the remaining time, in seconds.
+impl<C: Clock> fmt::Display for Countdown<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.remaining().as_secs_f64();
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*}s", precision, secs),
+            None => write!(f, "{}s", secs),
+        }
+    }
+}
+
+impl<C: Clock> Countdown<C> {
+    /// Creates a stopped countdown from `target`, which measures time using
+    /// `clock`.
+    pub fn with_clock(target: Duration, clock: C) -> Self {
+        Countdown {
+            stopwatch: Stopwatch::with_clock(clock),
+            target,
+        }
+    }
+
+    /// Starts or resumes the countdown.
+    ///
+    /// See `Stopwatch::start`.
+    pub fn start(&mut self) -> Option<TimeSpan<C>> {
+        self.stopwatch.start()
+    }
+
+    /// Pauses the countdown.
+    ///
+    /// See `Stopwatch::stop`.
+    pub fn stop(&mut self) -> Option<TimeSpan<C>> {
+        self.stopwatch.stop()
+    }
+
+    /// Resumes the countdown if it is paused, otherwise does nothing.
+    ///
+    /// See `Stopwatch::resume`.
+    pub fn resume(&mut self) -> Transition {
+        self.stopwatch.resume()
+    }
+
+    /// Pauses the countdown if it is running, otherwise does nothing.
+    ///
+    /// See `Stopwatch::pause`.
+    pub fn pause(&mut self) -> Transition {
+        self.stopwatch.pause()
+    }
+
+    /// Returns whether the countdown is running.
+    pub fn is_running(&self) -> bool {
+        self.stopwatch.is_running()
+    }
+
+    /// Returns the duration the countdown started from.
+    pub fn target(&self) -> Duration {
+        self.target
+    }
+
+    /// Returns the time the countdown has been running.
+    pub fn elapsed(&self) -> Duration {
+        self.stopwatch.elapsed()
+    }
+
+    /// Returns the time left before the countdown expires, which is zero once
+    /// it has.
+    pub fn remaining(&self) -> Duration {
+        self.target.saturating_sub(self.elapsed())
+    }
+
+    /// Returns whether the countdown has run for at least its target.
+    pub fn is_expired(&self) -> bool {
+        self.elapsed() >= self.target
+    }
+
+    /// Returns how long the countdown has run past its target, which is zero
+    /// until it expires.
+    pub fn overshoot(&self) -> Duration {
+        self.elapsed().saturating_sub(self.target)
+    }
+
+    /// Stops the countdown and restores its full target.
+    pub fn reset(&mut self) {
+        self.stopwatch.reset();
+    }
+
+    /// Stops the countdown and sets a new target.
+    pub fn reset_with(&mut self, target: Duration) {
+        self.reset();
+        self.target = target;
+    }
+
+    /// Returns the stopwatch measuring the time elapsed.
+    pub fn stopwatch(&self) -> &Stopwatch<C> {
+        &self.stopwatch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn counts_down() {
+        let clock = SharedManualClock::default();
+        let mut c = Countdown::with_clock(ms(100), clock.clone());
+        assert_eq!(c.remaining(), ms(100));
+        c.start();
+        clock.advance(ms(30));
+        assert_eq!(c.remaining(), ms(70));
+        c.stop();
+        clock.advance(ms(500));
+        assert_eq!(c.remaining(), ms(70));
+        assert!(!c.is_expired());
+        assert_eq!(c.overshoot(), Duration::ZERO);
+
+        assert_eq!(c.pause(), Transition::Unchanged);
+        assert_eq!(c.resume(), Transition::Started);
+        clock.advance(ms(70));
+        assert!(c.is_expired());
+        assert_eq!(c.remaining(), Duration::ZERO);
+        clock.advance(ms(20));
+        assert_eq!(c.overshoot(), ms(20));
+        assert_eq!(c.stopwatch().spans().len(), 2);
+        assert_eq!(format!("{:.2}", c), "0.00s");
+    }
+
+    #[test]
+    fn reset() {
+        let clock = SharedManualClock::default();
+        let mut c = Countdown::with_clock(ms(100), clock.clone());
+        c.start();
+        clock.advance(ms(150));
+        c.reset();
+        assert!(!c.is_running());
+        assert_eq!(c.remaining(), ms(100));
+        c.reset_with(ms(10));
+        assert_eq!(c.target(), ms(10));
+        c.start();
+        clock.advance(ms(4));
+        assert_eq!(c.to_string(), "0.006s");
+    }
+}
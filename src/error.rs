@@ -0,0 +1,137 @@
+use std::error::Error;
+use std::fmt;
+
+/// The reason a time span could not be added to a `Stopwatch`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpanError {
+    /// The time span stops before it starts.
+    StopBeforeStart,
+    /// The time span would not be the last one, but it is still running, or it
+    /// would come after the running time span of the stopwatch.
+    ///
+    /// Only the last time span of a stopwatch may be running.
+    RunningNotLast,
+    /// The time span would stop before the time span ahead of it, or after
+    /// the one following it.
+    ///
+    /// The time spans of a stopwatch are ordered by stop.
+    OutOfOrder,
+    /// The index is past the end of the time spans.
+    OutOfBounds {
+        /// The index at which the time span was inserted.
+        index: usize,
+        /// The number of time spans.
+        len: usize,
+    },
+}
+
+impl fmt::Display for SpanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpanError::StopBeforeStart => write!(f, "the time span stops before it starts"),
+            SpanError::RunningNotLast => {
+                write!(f, "only the last time span of a stopwatch may be running")
+            }
+            SpanError::OutOfOrder => {
+                write!(f, "the time spans of a stopwatch must be ordered by stop")
+            }
+            SpanError::OutOfBounds { index, len } => write!(
+                f,
+                "insertion index {} is out of bounds for {} time spans",
+                index, len
+            ),
+        }
+    }
+}
+
+impl Error for SpanError {}
+
+/// The reason the elapsed time of a `Stopwatch` could not be computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ElapsedError {
+    /// The time span at `index` ends before it starts, which means the clock
+    /// went backwards while it was running.
+    ClockWentBackwards {
+        /// The index of the time span.
+        index: usize,
+    },
+    /// The total elapsed time is too large to be represented by a `Duration`.
+    Overflow,
+}
+
+impl fmt::Display for ElapsedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ElapsedError::ClockWentBackwards { index } => write!(
+                f,
+                "the clock went backwards while time span {} was running",
+                index
+            ),
+            ElapsedError::Overflow => write!(f, "the elapsed time overflowed"),
+        }
+    }
+}
+
+impl Error for ElapsedError {}
+
+/// The reason CSV or JSON Lines data could not be parsed into a `Stopwatch`.
+///
+/// Lines are numbered from one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParseError {
+    /// The line is not a valid CSV record or JSON object.
+    Syntax {
+        /// The line of the error.
+        line: usize,
+    },
+    /// A required column or field is missing.
+    Missing {
+        /// The line of the error.
+        line: usize,
+        /// The name of the column or field.
+        field: &'static str,
+    },
+    /// A value is not a valid number of seconds.
+    InvalidValue {
+        /// The line of the error.
+        line: usize,
+        /// The name of the column or field.
+        field: &'static str,
+    },
+    /// The offsets are too large to be represented by instants.
+    OutOfRange,
+    /// The time span could not be added to the stopwatch.
+    Span {
+        /// The line of the error.
+        line: usize,
+        /// Why the time span could not be added.
+        error: SpanError,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Syntax { line } => write!(f, "invalid syntax on line {}", line),
+            ParseError::Missing { line, field } => {
+                write!(f, "missing `{}` on line {}", field, line)
+            }
+            ParseError::InvalidValue { line, field } => {
+                write!(f, "invalid `{}` on line {}", field, line)
+            }
+            ParseError::OutOfRange => {
+                write!(f, "the offsets cannot be represented by instants")
+            }
+            ParseError::Span { line, error } => write!(f, "{} on line {}", error, line),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Span { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,600 @@
+//! Export and import of time spans as CSV and JSON Lines, for offline analysis.
+//!
+//! Each time span is a row with the columns `index`, `label`, `start_offset`,
+//! `stop_offset` and `duration`. Offsets are in seconds from the earliest
+//! start, with nanosecond precision. A running time span has no
+//! stop offset and its duration is measured at the time of the export.
+
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::io;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{Duration, Instant};
+
+use crate::{write_json_str, Clock, ParseError, StdClock, Stopwatch, TimeSpan};
+
+const COLUMNS: &str = "index,label,start_offset,stop_offset,duration";
+
+impl<C: Clock> Stopwatch<C> {
+    /// Returns the time spans as CSV, with a header.
+    ///
+    /// Time spans evicted by the history limit are not included.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        self.fmt_csv(&mut csv).unwrap();
+        csv
+    }
+
+    /// Writes the time spans as CSV to `writer`, see `to_csv`.
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(self.to_csv().as_bytes())
+    }
+
+    /// Returns the time spans as JSON Lines, one object per time span.
+    ///
+    /// Time spans evicted by the history limit are not included.
+    pub fn to_json_lines(&self) -> String {
+        let mut json = String::new();
+        self.fmt_json_lines(&mut json).unwrap();
+        json
+    }
+
+    /// Writes the time spans as JSON Lines to `writer`, see `to_json_lines`.
+    pub fn write_json_lines(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(self.to_json_lines().as_bytes())
+    }
+
+    fn fmt_csv(&self, f: &mut String) -> fmt::Result {
+        writeln!(f, "{}", COLUMNS)?;
+        let Some(epoch) = self.epoch() else {
+            return Ok(());
+        };
+        for (index, span) in self.iter().enumerate() {
+            write!(f, "{},", index)?;
+            match &span.label {
+                Some(label) if label.is_empty() || label.contains([',', '"', '\n', '\r']) => {
+                    write!(f, "\"{}\"", label.replace('"', "\"\""))?
+                }
+                Some(label) => f.write_str(label)?,
+                None => {}
+            }
+            let (start, stop) = self.offsets(epoch, span);
+            write!(f, ",{},", Seconds(start))?;
+            if let Some(stop) = stop {
+                write!(f, "{}", Seconds(stop))?;
+            }
+            writeln!(f, ",{}", Seconds(span.elapsed_with(&self.clock)))?;
+        }
+        Ok(())
+    }
+
+    fn fmt_json_lines(&self, f: &mut String) -> fmt::Result {
+        let Some(epoch) = self.epoch() else {
+            return Ok(());
+        };
+        for (index, span) in self.iter().enumerate() {
+            write!(f, r#"{{"index":{},"label":"#, index)?;
+            match &span.label {
+                Some(label) => write_json_str(f, label)?,
+                None => f.write_str("null")?,
+            }
+            let (start, stop) = self.offsets(epoch, span);
+            write!(f, r#","start_offset":{},"stop_offset":"#, Seconds(start))?;
+            match stop {
+                Some(stop) => write!(f, "{}", Seconds(stop))?,
+                None => f.write_str("null")?,
+            }
+            writeln!(
+                f,
+                r#","duration":{}}}"#,
+                Seconds(span.elapsed_with(&self.clock))
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the earliest start, which is not necessarily the one of the
+    /// first time span, or `None` if there are no time spans.
+    fn epoch(&self) -> Option<C::Instant> {
+        self.iter().map(|span| span.start).min()
+    }
+
+    /// Returns the offsets of the start and stop of `span` from `epoch`.
+    fn offsets(&self, epoch: C::Instant, span: &TimeSpan<C>) -> (Duration, Option<Duration>) {
+        let offset = |instant| self.clock.duration_between(epoch, instant);
+        (offset(span.start), span.stop.map(offset))
+    }
+}
+
+impl Stopwatch {
+    /// Parses CSV written by `to_csv` back into a stopwatch.
+    ///
+    /// Columns are found by their name in the header, only `start_offset` is
+    /// required. An empty `stop_offset` makes the time span running. The
+    /// time spans are placed so that the latest offset is now.
+    pub fn from_csv(csv: &str) -> Result<Stopwatch, ParseError> {
+        let mut records = parse_csv(csv)?.into_iter();
+        let Some((_, header)) = records.next() else {
+            return Ok(Stopwatch::default());
+        };
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|field| field.as_deref() == Some(name))
+        };
+        let (label, start, stop, duration) = (
+            column("label"),
+            column("start_offset"),
+            column("stop_offset"),
+            column("duration"),
+        );
+        let Some(start) = start else {
+            return Err(ParseError::Missing {
+                line: 1,
+                field: "start_offset",
+            });
+        };
+        let rows = records
+            .map(|(line, mut fields)| {
+                let mut take = |column: Option<usize>| {
+                    column.and_then(|i| fields.get_mut(i).and_then(Option::take))
+                };
+                let label = take(label);
+                let start = take(Some(start));
+                let stop = take(stop);
+                let duration = take(duration);
+                Ok(Row {
+                    line,
+                    label,
+                    start: parse_offset(start.as_deref(), line, "start_offset")?.ok_or(
+                        ParseError::Missing {
+                            line,
+                            field: "start_offset",
+                        },
+                    )?,
+                    stop: parse_offset(stop.as_deref(), line, "stop_offset")?,
+                    duration: parse_offset(duration.as_deref(), line, "duration")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        build(rows)
+    }
+
+    /// Parses JSON Lines written by `to_json_lines` back into a stopwatch.
+    ///
+    /// Only the `start_offset` field is required. A missing or `null`
+    /// `stop_offset` makes the time span running. The time spans are placed
+    /// so that the latest offset is now.
+    pub fn from_json_lines(json: &str) -> Result<Stopwatch, ParseError> {
+        let rows = json
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(i, text)| {
+                let line = i + 1;
+                let mut row = Row {
+                    line,
+                    label: None,
+                    start: Duration::ZERO,
+                    stop: None,
+                    duration: None,
+                };
+                let mut has_start = false;
+                let fields = parse_json_object(text).ok_or(ParseError::Syntax { line })?;
+                for (key, value) in fields {
+                    match (key.as_str(), value) {
+                        ("label", JsonValue::String(label)) => row.label = Some(label),
+                        ("start_offset", JsonValue::Number(start)) => {
+                            row.start = parse_offset(Some(&start), line, "start_offset")?.unwrap();
+                            has_start = true;
+                        }
+                        ("stop_offset", JsonValue::Number(stop)) => {
+                            row.stop = parse_offset(Some(&stop), line, "stop_offset")?;
+                        }
+                        ("duration", JsonValue::Number(duration)) => {
+                            row.duration = parse_offset(Some(&duration), line, "duration")?;
+                        }
+                        ("start_offset", _) => {
+                            return Err(ParseError::InvalidValue {
+                                line,
+                                field: "start_offset",
+                            })
+                        }
+                        ("stop_offset", JsonValue::String(_)) => {
+                            return Err(ParseError::InvalidValue {
+                                line,
+                                field: "stop_offset",
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+                if !has_start {
+                    return Err(ParseError::Missing {
+                        line,
+                        field: "start_offset",
+                    });
+                }
+                Ok(row)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        build(rows)
+    }
+}
+
+/// Formats a duration as seconds with nanosecond precision.
+struct Seconds(Duration);
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:09}", self.0.as_secs(), self.0.subsec_nanos())
+    }
+}
+
+/// A parsed time span.
+struct Row {
+    line: usize,
+    label: Option<String>,
+    start: Duration,
+    stop: Option<Duration>,
+    duration: Option<Duration>,
+}
+
+/// Creates a stopwatch from `rows`, placing them so that the latest offset
+/// is now.
+///
+/// The duration of a running time span is the time it ran for until the
+/// export, so it counts as an offset too.
+fn build(rows: Vec<Row>) -> Result<Stopwatch, ParseError> {
+    let latest = rows
+        .iter()
+        .flat_map(|row| {
+            let exported = row.duration.filter(|_| row.stop.is_none());
+            [
+                row.stop,
+                exported.map(|duration| row.start.saturating_add(duration)),
+            ]
+        })
+        .flatten()
+        .chain(rows.iter().map(|row| row.start))
+        .max()
+        .unwrap_or_default();
+    let epoch = Instant::now()
+        .checked_sub(latest)
+        .ok_or(ParseError::OutOfRange)?;
+    let mut stopwatch = Stopwatch::with_clock(StdClock);
+    for row in rows {
+        stopwatch
+            .push_span(TimeSpan {
+                start: epoch + row.start,
+                stop: row.stop.map(|stop| epoch + stop),
+                label: row.label.map(Cow::Owned),
+            })
+            .map_err(|error| ParseError::Span {
+                line: row.line,
+                error,
+            })?;
+    }
+    Ok(stopwatch)
+}
+
+/// Parses a number of seconds, or returns `None` if there is no value.
+fn parse_offset(
+    value: Option<&str>,
+    line: usize,
+    field: &'static str,
+) -> Result<Option<Duration>, ParseError> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    parse_seconds(value)
+        .map(Some)
+        .ok_or(ParseError::InvalidValue { line, field })
+}
+
+/// Parses a number of seconds exactly if it has at most nine decimals, or
+/// approximately otherwise, such as for scientific notation.
+fn parse_seconds(value: &str) -> Option<Duration> {
+    let (secs, frac) = value.split_once('.').unwrap_or((value, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if !secs.is_empty() && digits(secs) && digits(frac) && frac.len() <= 9 {
+        let nanos = format!("{:0<9}", frac).parse().ok()?;
+        return Some(Duration::new(secs.parse().ok()?, nanos));
+    }
+    Duration::try_from_secs_f64(value.parse().ok()?).ok()
+}
+
+/// A CSV record, along with its first line.
+type Record = (usize, Vec<Option<String>>);
+
+/// Parses CSV into records of fields. Empty, unquoted fields are `None`, and
+/// blank lines are skipped.
+fn parse_csv(csv: &str) -> Result<Vec<Record>, ParseError> {
+    let mut records = Vec::new();
+    let mut chars = csv.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let record_line = line;
+        let mut fields = Vec::new();
+        loop {
+            let mut field = String::new();
+            let quoted = chars.next_if_eq(&'"').is_some();
+            if quoted {
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.next_if_eq(&'"').is_none() => break,
+                        Some(c) => {
+                            line += (c == '\n') as usize;
+                            field.push(c);
+                        }
+                        None => return Err(ParseError::Syntax { line: record_line }),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|&c| !matches!(c, ',' | '\r' | '\n')) {
+                    if c == '"' {
+                        return Err(ParseError::Syntax { line });
+                    }
+                    field.push(c);
+                }
+            }
+            fields.push((quoted || !field.is_empty()).then_some(field));
+            match chars.next() {
+                Some(',') => {}
+                Some('\r') | Some('\n') | None => {
+                    chars.next_if_eq(&'\n');
+                    line += 1;
+                    break;
+                }
+                Some(_) => return Err(ParseError::Syntax { line }),
+            }
+        }
+        if fields != [None] {
+            records.push((record_line, fields));
+        }
+    }
+    Ok(records)
+}
+
+enum JsonValue {
+    Null,
+    String(String),
+    Number(String),
+}
+
+/// Parses a JSON object whose values are strings, numbers or `null`.
+fn parse_json_object(text: &str) -> Option<Vec<(String, JsonValue)>> {
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+        chars.next_if_eq(&'"')?;
+        let mut string = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(string),
+                '\\' => match chars.next()? {
+                    'u' => {
+                        let mut code = parse_hex(chars)?;
+                        if (0xD800..0xDC00).contains(&code) {
+                            chars.next_if_eq(&'\\')?;
+                            chars.next_if_eq(&'u')?;
+                            let low = parse_hex(chars)?;
+                            code = 0x10000 + ((code - 0xD800) << 10) + low.checked_sub(0xDC00)?;
+                        }
+                        string.push(char::from_u32(code)?);
+                    }
+                    c => string.push(match c {
+                        '"' | '\\' | '/' => c,
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        _ => return None,
+                    }),
+                },
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+        (0..4).try_fold(0, |code, _| Some(code * 16 + chars.next()?.to_digit(16)?))
+    }
+
+    let mut chars = text.chars().peekable();
+    let mut fields = Vec::new();
+    skip_whitespace(&mut chars);
+    chars.next_if_eq(&'{')?;
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_none() {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            chars.next_if_eq(&':')?;
+            skip_whitespace(&mut chars);
+            let value = match chars.peek()? {
+                '"' => JsonValue::String(parse_string(&mut chars)?),
+                'n' => {
+                    for c in "null".chars() {
+                        chars.next_if_eq(&c)?;
+                    }
+                    JsonValue::Null
+                }
+                _ => {
+                    let mut number = String::new();
+                    while let Some(c) = chars.next_if(|c| "+-.eE0123456789".contains(*c)) {
+                        number.push(c);
+                    }
+                    if number.is_empty() {
+                        return None;
+                    }
+                    JsonValue::Number(number)
+                }
+            };
+            fields.push((key, value));
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => {}
+                '}' => break,
+                _ => return None,
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    chars.peek().is_none().then_some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    fn sample(clock: &SharedManualClock) -> Stopwatch<SharedManualClock> {
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start_named("load, \"fast\"");
+        clock.advance(Duration::from_micros(1500));
+        sw.stop();
+        clock.advance(ms(1));
+        sw.start_named("");
+        clock.advance(ms(2));
+        sw.start();
+        clock.advance(ms(1));
+        sw
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let clock = SharedManualClock::default();
+        let csv = sample(&clock).to_csv();
+        assert_eq!(
+            csv,
+            "index,label,start_offset,stop_offset,duration\n\
+             0,\"load, \"\"fast\"\"\",0.000000000,0.001500000,0.001500000\n\
+             1,\"\",0.002500000,0.004500000,0.002000000\n\
+             2,,0.004500000,,0.001000000\n"
+        );
+
+        let sw = Stopwatch::from_csv(&csv).unwrap();
+        assert!(sw.is_running());
+        assert_eq!(sw.spans().len(), 3);
+        assert_eq!(sw.spans()[0].label.as_deref(), Some("load, \"fast\""));
+        assert_eq!(sw.spans()[1].label.as_deref(), Some(""));
+        assert_eq!(sw.spans()[2].label, None);
+        assert_eq!(Duration::from(sw.spans()[1].clone()), ms(2));
+        assert!(sw.elapsed() >= Duration::from_micros(4500));
+    }
+
+    #[test]
+    fn unordered_starts() {
+        let mut sw = Stopwatch::with_clock(SharedManualClock::default());
+        for (start, stop) in [(10, 20), (0, 30)] {
+            sw.push_span(TimeSpan {
+                start: ms(start),
+                stop: Some(ms(stop)),
+                label: None,
+            })
+            .unwrap();
+        }
+        let csv = sw.to_csv();
+        assert_eq!(
+            csv,
+            "index,label,start_offset,stop_offset,duration\n\
+             0,,0.010000000,0.020000000,0.010000000\n\
+             1,,0.000000000,0.030000000,0.030000000\n"
+        );
+        assert!(sw.to_json_lines().contains(r#""start_offset":0.010000000"#));
+
+        let copy = Stopwatch::from_csv(&csv).unwrap();
+        assert_eq!(copy.spans()[0].start - copy.spans()[1].start, ms(10));
+        assert_eq!(copy.elapsed(), ms(40));
+    }
+
+    #[test]
+    fn csv_errors() {
+        let reordered = "stop_offset,start_offset\r\n\r\n1e-3,0\r\n";
+        let sw = Stopwatch::from_csv(reordered).unwrap();
+        assert_eq!(Duration::from(sw.spans()[0].clone()), ms(1));
+        assert_eq!(Stopwatch::from_csv("").unwrap().spans().len(), 0);
+
+        assert_eq!(
+            Stopwatch::from_csv("label\nx\n").unwrap_err(),
+            ParseError::Missing {
+                line: 1,
+                field: "start_offset"
+            }
+        );
+        assert_eq!(
+            Stopwatch::from_csv("start_offset\n\"\n").unwrap_err(),
+            ParseError::Syntax { line: 2 }
+        );
+        assert_eq!(
+            Stopwatch::from_csv("label,start_offset\n\"a\nb\",x\n").unwrap_err(),
+            ParseError::InvalidValue {
+                line: 2,
+                field: "start_offset"
+            }
+        );
+        assert_eq!(
+            Stopwatch::from_csv("start_offset,stop_offset\n0,\n1,2\n").unwrap_err(),
+            ParseError::Span {
+                line: 3,
+                error: SpanError::RunningNotLast
+            }
+        );
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let clock = SharedManualClock::default();
+        let json = sample(&clock).to_json_lines();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"index":0,"label":"load, \"fast\"","start_offset":0.000000000,"stop_offset":0.001500000,"duration":0.001500000}"#,
+                "\n",
+                r#"{"index":1,"label":"","start_offset":0.002500000,"stop_offset":0.004500000,"duration":0.002000000}"#,
+                "\n",
+                r#"{"index":2,"label":null,"start_offset":0.004500000,"stop_offset":null,"duration":0.001000000}"#,
+                "\n"
+            )
+        );
+
+        let sw = Stopwatch::from_json_lines(&json).unwrap();
+        assert!(sw.is_running());
+        assert_eq!(sw.spans()[0].label.as_deref(), Some("load, \"fast\""));
+        assert_eq!(
+            Duration::from(sw.spans()[0].clone()),
+            Duration::from_micros(1500)
+        );
+
+        let other = "\n { \"start_offset\" : 1 , \"label\": \"\\u00e9\\ud83d\\ude00\", \"stop_offset\": 2.5 }\n";
+        let sw = Stopwatch::from_json_lines(other).unwrap();
+        assert_eq!(sw.spans()[0].label.as_deref(), Some("é😀"));
+        assert_eq!(Duration::from(sw.spans()[0].clone()), ms(1500));
+
+        assert_eq!(
+            Stopwatch::from_json_lines("{\"start_offset\":0}\n{\"start_offset\":0").unwrap_err(),
+            ParseError::Syntax { line: 2 }
+        );
+        assert_eq!(
+            Stopwatch::from_json_lines("{\"stop_offset\":1}").unwrap_err(),
+            ParseError::Missing {
+                line: 1,
+                field: "start_offset"
+            }
+        );
+        assert_eq!(
+            Stopwatch::from_json_lines("{\"start_offset\":\"0\"}").unwrap_err(),
+            ParseError::InvalidValue {
+                line: 1,
+                field: "start_offset"
+            }
+        );
+    }
+}
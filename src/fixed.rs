@@ -0,0 +1,279 @@
+use core::fmt;
+use core::time::Duration;
+
+use crate::{Clock, Transition};
+
+/// The start and stop instants of a time span.
+type RawSpan<I> = (I, Option<I>);
+
+/// A stopwatch which stores its time spans inline, without allocating.
+///
+/// It keeps the last `N` time spans. Older ones are evicted but still count
+/// towards `elapsed`, like a `Stopwatch` with a history limit. It is available
+/// without the `std` feature, measuring time with a user-provided `Clock` such
+/// as `TickClock`.
+///
+/// Its methods mirror those of `Stopwatch`, except that time spans have no
+/// labels and are only their start and stop instants, so `start` and `stop`
+/// return the duration of the stopped time span instead of a `TimeSpan`.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// use core::sync::atomic::{AtomicU64, Ordering};
+///
+/// static MICROS: AtomicU64 = AtomicU64::new(0); // Updated by a hardware timer.
+///
+/// let clock = TickClock::new(|| MICROS.load(Ordering::Relaxed), 1_000_000);
+/// let mut s = FixedStopwatch::<_, 8>::with_clock(clock);
+/// s.start(); // Starts the stopwatch.
+/// s.start(); // Creates a new time span, evicting the oldest one if full.
+/// s.stop(); // Stops the stopwatch.
+/// let total_time = s.elapsed(); // returns the total time as a Duration.
+/// for (start, stop) in s.spans() {
+///     println!("{:?} -> {:?}", start, stop);
+/// }
+/// s.reset(); // Reset the stopwatch.
+/// ```
+pub struct FixedStopwatch<C: Clock, const N: usize> {
+    // ring buffer of `len` spans, the oldest at `first`.
+    spans: [Option<RawSpan<C::Instant>>; N],
+    first: usize,
+    len: usize,
+    evicted_count: usize,
+    evicted_elapsed: Duration,
+    clock: C,
+}
+
+impl<C: Clock + Clone, const N: usize> Clone for FixedStopwatch<C, N> {
+    fn clone(&self) -> Self {
+        FixedStopwatch {
+            spans: self.spans,
+            first: self.first,
+            len: self.len,
+            evicted_count: self.evicted_count,
+            evicted_elapsed: self.evicted_elapsed,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<C: Clock + fmt::Debug, const N: usize> fmt::Debug for FixedStopwatch<C, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FixedStopwatch")
+            .field("spans", &DebugSpans(self))
+            .field("evicted_count", &self.evicted_count)
+            .field("evicted_elapsed", &self.evicted_elapsed)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+struct DebugSpans<'a, C: Clock, const N: usize>(&'a FixedStopwatch<C, N>);
+
+impl<C: Clock, const N: usize> fmt::Debug for DebugSpans<'_, C, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.0.spans()).finish()
+    }
+}
+
+impl<C: Clock, const N: usize> FixedStopwatch<C, N> {
+    const NOT_EMPTY: () = assert!(N > 0, "a FixedStopwatch must hold at least one time span");
+
+    /// Creates a stopped stopwatch which measures time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NOT_EMPTY;
+        FixedStopwatch {
+            spans: [None; N],
+            first: 0,
+            len: 0,
+            evicted_count: 0,
+            evicted_elapsed: Duration::ZERO,
+            clock,
+        }
+    }
+
+    /// Returns the clock used by this stopwatch.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns the start and stop instants of the time spans, from oldest to
+    /// newest.
+    ///
+    /// Only the last time span may have no stop value, which means it is
+    /// still running.
+    pub fn spans(&self) -> impl Iterator<Item = (C::Instant, Option<C::Instant>)> + '_ {
+        (0..self.len).map(move |i| self.spans[(self.first + i) % N].unwrap())
+    }
+
+    /// Starts the stopwatch.
+    ///
+    /// If it is already started, it will create a new split. Returns the
+    /// duration of the time span which was stopped, if any.
+    pub fn start(&mut self) -> Option<Duration> {
+        let ret = self.stop();
+        if self.len == N {
+            let (start, stop) = self.spans[self.first].unwrap();
+            let elapsed = self.clock.duration_between(start, stop.unwrap());
+            self.evicted_elapsed = self.evicted_elapsed.saturating_add(elapsed);
+            self.evicted_count += 1;
+            self.first = (self.first + 1) % N;
+            self.len -= 1;
+        }
+        self.spans[(self.first + self.len) % N] = Some((self.clock.now(), None));
+        self.len += 1;
+        ret
+    }
+
+    /// Stops the stopwatch without resetting it. Returns the duration of the
+    /// time span which was stopped, if any.
+    pub fn stop(&mut self) -> Option<Duration> {
+        if !self.is_running() {
+            return None;
+        }
+        let now = self.clock.now();
+        let (start, stop) = self.last_mut().as_mut().unwrap();
+        *stop = Some(now);
+        let start = *start;
+        Some(self.clock.duration_between(start, now))
+    }
+
+    /// Starts the stopwatch if it is stopped, otherwise does nothing.
+    pub fn resume(&mut self) -> Transition {
+        if self.is_running() {
+            Transition::Unchanged
+        } else {
+            self.start();
+            Transition::Started
+        }
+    }
+
+    /// Stops the stopwatch if it is running, otherwise does nothing.
+    pub fn pause(&mut self) -> Transition {
+        match self.stop() {
+            Some(_) => Transition::Stopped,
+            None => Transition::Unchanged,
+        }
+    }
+
+    /// Stops the stopwatch if it is running, otherwise starts it.
+    pub fn toggle(&mut self) -> Transition {
+        match self.stop() {
+            Some(_) => Transition::Stopped,
+            None => {
+                self.start();
+                Transition::Started
+            }
+        }
+    }
+
+    /// Creates a new split if the stopwatch is running, otherwise does
+    /// nothing.
+    ///
+    /// Unlike `start`, this never starts a stopped stopwatch.
+    pub fn split(&mut self) -> Transition {
+        if self.is_running() {
+            self.start();
+            Transition::Split
+        } else {
+            Transition::Unchanged
+        }
+    }
+
+    /// Returns whether the stopwatch is running.
+    pub fn is_running(&self) -> bool {
+        self.len > 0 && self.spans[(self.first + self.len - 1) % N].is_some_and(|s| s.1.is_none())
+    }
+
+    /// Returns the total elapsed time accumulated inside of this stopwatch,
+    /// including the evicted time spans.
+    pub fn elapsed(&self) -> Duration {
+        self.spans()
+            .fold(self.evicted_elapsed, |total, (start, stop)| {
+                let stop = stop.unwrap_or_else(|| self.clock.now());
+                total.saturating_add(self.clock.duration_between(start, stop))
+            })
+    }
+
+    /// Returns the number of evicted time spans.
+    pub fn evicted_count(&self) -> usize {
+        self.evicted_count
+    }
+
+    /// Stops the stopwatch and removes all its time spans, including the
+    /// time accumulated by evicted ones.
+    pub fn reset(&mut self) {
+        self.spans = [None; N];
+        self.first = 0;
+        self.len = 0;
+        self.evicted_count = 0;
+        self.evicted_elapsed = Duration::ZERO;
+    }
+
+    fn last_mut(&mut self) -> &mut Option<RawSpan<C::Instant>> {
+        &mut self.spans[(self.first + self.len - 1) % N]
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn start_stop() {
+        let clock = ManualClock::new();
+        let mut sw = FixedStopwatch::<_, 2>::with_clock(&clock);
+        assert!(!sw.is_running());
+        assert_eq!(sw.stop(), None);
+        assert_eq!(sw.pause(), Transition::Unchanged);
+        assert_eq!(sw.start(), None);
+        clock.advance(ms(10));
+        assert_eq!(sw.stop(), Some(ms(10)));
+        clock.advance(ms(10));
+        assert_eq!(sw.resume(), Transition::Started);
+        clock.advance(ms(5));
+        assert!(sw.is_running());
+        assert_eq!(sw.elapsed(), ms(15));
+        assert_eq!(sw.spans().count(), 2);
+    }
+
+    #[test]
+    fn toggle_split() {
+        let clock = ManualClock::new();
+        let mut sw = FixedStopwatch::<_, 4>::with_clock(&clock);
+        assert_eq!(sw.split(), Transition::Unchanged);
+        assert_eq!(sw.toggle(), Transition::Started);
+        clock.advance(ms(3));
+        assert_eq!(sw.split(), Transition::Split);
+        clock.advance(ms(2));
+        assert_eq!(sw.toggle(), Transition::Stopped);
+        assert!(!sw.is_running());
+        assert_eq!(sw.elapsed(), ms(5));
+        let spans: Vec<_> = sw.spans().collect();
+        assert_eq!(spans, [(ms(0), Some(ms(3))), (ms(3), Some(ms(5)))]);
+    }
+
+    #[test]
+    fn evicts_oldest() {
+        let clock = ManualClock::new();
+        let mut sw = FixedStopwatch::<_, 3>::with_clock(&clock);
+        for _ in 0..10 {
+            sw.start();
+            clock.advance(ms(1));
+        }
+        assert_eq!(sw.evicted_count(), 7);
+        assert_eq!(sw.elapsed(), ms(10));
+        let spans: Vec<_> = sw.spans().collect();
+        assert_eq!(
+            spans,
+            [(ms(7), Some(ms(8))), (ms(8), Some(ms(9))), (ms(9), None)]
+        );
+        sw.reset();
+        assert_eq!(sw.elapsed(), Duration::ZERO);
+        assert!(!sw.is_running());
+    }
+}
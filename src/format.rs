@@ -0,0 +1,305 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Clock, Stopwatch, TimeSpan};
+
+/// A unit of time used to format durations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeUnit {
+    /// Nanoseconds, printed as `ns`.
+    Nanoseconds,
+    /// Microseconds, printed as `µs`.
+    Microseconds,
+    /// Milliseconds, printed as `ms`.
+    Milliseconds,
+    /// Seconds, printed as `s`.
+    Seconds,
+    /// Minutes, printed as `min`.
+    Minutes,
+    /// Hours, printed as `h`.
+    Hours,
+}
+
+impl TimeUnit {
+    const ALL: [TimeUnit; 6] = [
+        TimeUnit::Hours,
+        TimeUnit::Minutes,
+        TimeUnit::Seconds,
+        TimeUnit::Milliseconds,
+        TimeUnit::Microseconds,
+        TimeUnit::Nanoseconds,
+    ];
+
+    /// Returns the largest unit in which `duration` is at least one, or
+    /// nanoseconds for durations shorter than a microsecond.
+    pub fn fitting(duration: Duration) -> TimeUnit {
+        Self::ALL
+            .into_iter()
+            .find(|unit| duration.as_nanos() >= unit.nanos())
+            .unwrap_or(TimeUnit::Nanoseconds)
+    }
+
+    /// Returns the symbol of this unit.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TimeUnit::Nanoseconds => "ns",
+            TimeUnit::Microseconds => "µs",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Seconds => "s",
+            TimeUnit::Minutes => "min",
+            TimeUnit::Hours => "h",
+        }
+    }
+
+    fn nanos(self) -> u128 {
+        match self {
+            TimeUnit::Nanoseconds => 1,
+            TimeUnit::Microseconds => 1_000,
+            TimeUnit::Milliseconds => 1_000_000,
+            TimeUnit::Seconds => 1_000_000_000,
+            TimeUnit::Minutes => 60_000_000_000,
+            TimeUnit::Hours => 3_600_000_000_000,
+        }
+    }
+}
+
+/// How a duration is laid out when formatted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FormatStyle {
+    /// A number in the largest unit in which the duration is at least one,
+    /// such as `50.123ms`.
+    #[default]
+    Auto,
+    /// A number in the given unit, such as `0.050123s`.
+    Unit(TimeUnit),
+    /// A clock, such as `01:02:03.456`.
+    Clock,
+}
+
+/// Options used to format durations.
+///
+/// The precision is the number of decimal digits printed. When formatting,
+/// a precision given in the format string, as in `{:.3}`, takes precedence.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use std::time::Duration;
+///
+/// let d = Duration::from_micros(50_123);
+/// assert_eq!(d.display_with(FormatOptions::auto()).to_string(), "50.123ms");
+/// assert_eq!(format!("{:.1}", d.display_with(FormatOptions::auto())), "50.1ms");
+/// let seconds = FormatOptions::unit(TimeUnit::Seconds).precision(2);
+/// assert_eq!(d.display_with(seconds).to_string(), "0.05s");
+/// let clock = Duration::from_millis(3_723_456).display_with(FormatOptions::clock());
+/// assert_eq!(clock.to_string(), "01:02:03.456");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FormatOptions {
+    /// The layout of the formatted duration.
+    pub style: FormatStyle,
+    /// The number of decimal digits, if fixed.
+    pub precision: Option<usize>,
+}
+
+impl FormatOptions {
+    /// Formats durations in the largest fitting unit.
+    pub fn auto() -> Self {
+        Self::default()
+    }
+
+    /// Formats durations in `unit`.
+    pub fn unit(unit: TimeUnit) -> Self {
+        FormatOptions {
+            style: FormatStyle::Unit(unit),
+            precision: None,
+        }
+    }
+
+    /// Formats durations as a clock, with millisecond precision by default.
+    pub fn clock() -> Self {
+        FormatOptions {
+            style: FormatStyle::Clock,
+            precision: None,
+        }
+    }
+
+    /// Sets the number of decimal digits.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+}
+
+/// Formats a duration according to `FormatOptions`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayDuration {
+    duration: Duration,
+    options: FormatOptions,
+}
+
+impl DisplayDuration {
+    /// Creates an adapter formatting `duration` with `options`.
+    pub fn new(duration: Duration, options: FormatOptions) -> Self {
+        DisplayDuration { duration, options }
+    }
+}
+
+impl fmt::Display for DisplayDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().or(self.options.precision);
+        let unit = match self.options.style {
+            FormatStyle::Auto => TimeUnit::fitting(self.duration),
+            FormatStyle::Unit(unit) => unit,
+            FormatStyle::Clock => return fmt_clock(self.duration, precision.unwrap_or(3), f),
+        };
+        let value = self.duration.as_nanos() as f64 / unit.nanos() as f64;
+        match precision {
+            Some(precision) => write!(f, "{:.*}{}", precision, value, unit.symbol()),
+            None => write!(f, "{}{}", value, unit.symbol()),
+        }
+    }
+}
+
+fn fmt_clock(duration: Duration, precision: usize, f: &mut fmt::Formatter) -> fmt::Result {
+    // round to the last printed digit, which may carry into the seconds.
+    let digits = precision.min(9);
+    let step = 10u128.pow(9 - digits as u32);
+    let nanos = (duration.as_nanos() + step / 2) / step * step;
+    let secs = nanos / 1_000_000_000;
+    write!(
+        f,
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )?;
+    if precision > 0 {
+        let fraction = nanos % 1_000_000_000 / step;
+        write!(
+            f,
+            ".{:0digits$}{:0<pad$}",
+            fraction,
+            "",
+            pad = precision - digits
+        )?;
+    }
+    Ok(())
+}
+
+/// Formats durations with `FormatOptions`.
+pub trait FormatDuration {
+    /// Returns an adapter formatting this duration with `options`.
+    fn display_with(&self, options: FormatOptions) -> DisplayDuration;
+}
+
+impl FormatDuration for Duration {
+    fn display_with(&self, options: FormatOptions) -> DisplayDuration {
+        DisplayDuration::new(*self, options)
+    }
+}
+
+impl TimeSpan {
+    /// Returns an adapter formatting the duration of this span with
+    /// `options`.
+    ///
+    /// A running span is measured when this is called.
+    pub fn display_with(&self, options: FormatOptions) -> DisplayDuration {
+        DisplayDuration::new(self.clone().into(), options)
+    }
+}
+
+impl<C: Clock> Stopwatch<C> {
+    /// Returns an adapter formatting the total elapsed time with `options`.
+    ///
+    /// The elapsed time is measured when this is called.
+    /// # Example
+    /// ```rust
+    /// use stopwatch2::*;
+    ///
+    /// let mut s = Stopwatch::default();
+    /// s.start();
+    /// s.stop();
+    /// println!("{}", s.display_with(FormatOptions::auto())); // Prints e.g. `1.234µs`.
+    /// println!("{:.1}", s.display_with(FormatOptions::auto())); // Prints e.g. `1.2µs`.
+    /// println!("{}", s.display_with(FormatOptions::clock())); // Prints `00:00:00.000`.
+    /// ```
+    pub fn display_with(&self, options: FormatOptions) -> DisplayDuration {
+        DisplayDuration::new(self.elapsed(), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+
+    fn auto(duration: Duration) -> String {
+        duration.display_with(FormatOptions::auto()).to_string()
+    }
+
+    #[test]
+    fn auto_units() {
+        assert_eq!(auto(Duration::ZERO), "0ns");
+        assert_eq!(auto(Duration::from_nanos(999)), "999ns");
+        assert_eq!(auto(Duration::from_nanos(1_500)), "1.5µs");
+        assert_eq!(auto(Duration::from_millis(50)), "50ms");
+        assert_eq!(auto(Duration::from_millis(2_500)), "2.5s");
+        assert_eq!(auto(Duration::from_secs(90)), "1.5min");
+        assert_eq!(auto(Duration::from_secs(5_400)), "1.5h");
+    }
+
+    #[test]
+    fn precision() {
+        let options = FormatOptions::unit(TimeUnit::Milliseconds).precision(2);
+        let d = Duration::from_micros(1_234_567);
+        assert_eq!(d.display_with(options).to_string(), "1234.57ms");
+        assert_eq!(format!("{:.0}", d.display_with(options)), "1235ms");
+    }
+
+    #[test]
+    fn clock_style() {
+        let d = Duration::new(3_723, 456_789_000);
+        assert_eq!(
+            d.display_with(FormatOptions::clock()).to_string(),
+            "01:02:03.457"
+        );
+        assert_eq!(
+            format!("{:.0}", d.display_with(FormatOptions::clock())),
+            "01:02:03"
+        );
+        assert_eq!(
+            format!("{:.12}", d.display_with(FormatOptions::clock())),
+            "01:02:03.456789000000"
+        );
+        let carry = Duration::from_millis(599_996);
+        assert_eq!(
+            format!("{:.2}", carry.display_with(FormatOptions::clock())),
+            "00:10:00.00"
+        );
+        let long = Duration::from_secs(100 * 3600);
+        assert_eq!(
+            format!("{:.0}", long.display_with(FormatOptions::clock())),
+            "100:00:00"
+        );
+    }
+
+    #[test]
+    fn stopwatch_and_span() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start();
+        clock.advance(Duration::from_millis(1_500));
+        sw.stop();
+        assert_eq!(sw.display_with(FormatOptions::auto()).to_string(), "1.5s");
+        assert_eq!(format!("{:.3}", sw), "1.500s");
+        assert_eq!(sw.to_string(), "1.5s");
+
+        let mut sw = Stopwatch::default();
+        sw.start();
+        let span = sw.stop().unwrap();
+        assert!(span
+            .display_with(FormatOptions::clock())
+            .to_string()
+            .starts_with("00:00:00."));
+    }
+}
@@ -0,0 +1,168 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+
+use crate::{Clock, Stopwatch};
+
+/// How long a future took to complete, measured by `Timed`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct PollReport {
+    /// The time from the first poll of the future to its completion.
+    pub wall: Duration,
+    /// The time spent inside of `poll`.
+    pub busy: Duration,
+    /// The number of times the future was polled.
+    pub polls: usize,
+}
+
+impl PollReport {
+    /// Returns the time the future spent pending, outside of `poll`.
+    pub fn idle(&self) -> Duration {
+        self.wall.saturating_sub(self.busy)
+    }
+}
+
+pin_project! {
+    /// A future measuring the time spent polling another one.
+    ///
+    /// Created by `FutureExt::timed`. Each poll is added to a stopwatch as a
+    /// time span, and the output comes with a `PollReport`. It works with any
+    /// runtime.
+    /// # Example
+    /// ```rust
+    /// use stopwatch2::*;
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::task::{Context, Waker};
+    ///
+    /// let mut s = Stopwatch::default();
+    /// let mut future = pin!(async { 42 }.timed(&mut s));
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// let (value, report) = loop {
+    ///     if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+    ///         break output;
+    ///     }
+    /// };
+    /// assert_eq!(value, 42);
+    /// assert_eq!(report.polls, 1);
+    /// println!("busy {:?} of {:?}", report.busy, report.wall);
+    /// ```
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless polled"]
+    pub struct Timed<'a, F, C: Clock> {
+        #[pin]
+        future: F,
+        stopwatch: &'a mut Stopwatch<C>,
+        first_poll: Option<C::Instant>,
+        report: PollReport,
+    }
+}
+
+impl<F: Future, C: Clock> Future for Timed<'_, F, C> {
+    type Output = (F::Output, PollReport);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let start = this.stopwatch.clock().now();
+        let first_poll = *this.first_poll.get_or_insert(start);
+        let poll = this.future.poll(cx);
+        let clock = this.stopwatch.clock();
+        let stop = clock.now();
+        let report = this.report;
+        report.busy = report
+            .busy
+            .saturating_add(clock.duration_between(start, stop));
+        report.polls += 1;
+        if poll.is_ready() {
+            report.wall = clock.duration_between(first_poll, stop);
+        }
+        this.stopwatch.record_stopped(start, stop, None);
+        poll.map(|output| (output, *report))
+    }
+}
+
+/// Adds `timed` to all futures.
+pub trait FutureExt: Future + Sized {
+    /// Measures the time spent polling this future, adding each poll to
+    /// `stopwatch` as a time span.
+    ///
+    /// The output of the returned future is the output of this one, along
+    /// with its wall time, busy time and number of polls.
+    fn timed<C: Clock>(self, stopwatch: &mut Stopwatch<C>) -> Timed<'_, Self, C> {
+        Timed {
+            future: self,
+            stopwatch,
+            first_poll: None,
+            report: PollReport::default(),
+        }
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{block_on, ms};
+    use crate::*;
+    use std::future::poll_fn;
+    use std::task::Poll;
+
+    #[test]
+    fn busy_and_wall_time() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        let mut remaining = 2;
+        let future = poll_fn(|_| {
+            clock.advance(ms(1));
+            if remaining == 0 {
+                Poll::Ready("done")
+            } else {
+                remaining -= 1;
+                Poll::Pending
+            }
+        });
+        let (output, report) = block_on(future.timed(&mut sw), || clock.advance(ms(10)));
+        assert_eq!(output, "done");
+        assert_eq!(
+            report,
+            PollReport {
+                wall: ms(23),
+                busy: ms(3),
+                polls: 3,
+            }
+        );
+        assert_eq!(report.idle(), ms(20));
+        assert_eq!(sw.spans().len(), 3);
+        assert_eq!(sw.elapsed(), ms(3));
+    }
+
+    #[test]
+    fn async_blocks() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start();
+        let inner = async {
+            let mut yielded = false;
+            poll_fn(|cx| {
+                if yielded {
+                    Poll::Ready(())
+                } else {
+                    yielded = true;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            })
+            .await;
+            7
+        };
+        let (value, report) = block_on(inner.timed(&mut sw), || {});
+        assert_eq!(value, 7);
+        assert_eq!(report.polls, 2);
+        // the running span is kept last.
+        assert!(sw.is_running());
+        assert_eq!(sw.spans().len(), 3);
+    }
+}
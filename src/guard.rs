@@ -0,0 +1,151 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{Clock, StdClock, Stopwatch};
+
+/// Stops a borrowed stopwatch when dropped.
+///
+/// Created by `Stopwatch::scoped`. Because the stopwatch is stopped on drop,
+/// every way out of the scope is measured, including early returns, `?` and
+/// panics.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut s = Stopwatch::default();
+/// {
+///     let _guard = s.scoped(); // Starts the stopwatch.
+///     // Do some work...
+/// } // Stops the stopwatch.
+/// assert!(!s.is_running());
+/// ```
+#[derive(Debug)]
+pub struct StopwatchGuard<'a, C: Clock = StdClock> {
+    stopwatch: &'a mut Stopwatch<C>,
+}
+
+impl<C: Clock> Deref for StopwatchGuard<'_, C> {
+    type Target = Stopwatch<C>;
+
+    fn deref(&self) -> &Stopwatch<C> {
+        self.stopwatch
+    }
+}
+
+impl<C: Clock> Drop for StopwatchGuard<'_, C> {
+    fn drop(&mut self) {
+        self.stopwatch.stop();
+    }
+}
+
+/// Owns a running stopwatch and stops it when dropped or finished.
+///
+/// Created by `Stopwatch::into_scoped`. Use `finish` to stop the stopwatch and
+/// get it back.
+#[derive(Debug)]
+pub struct OwnedStopwatchGuard<C: Clock = StdClock> {
+    // only `None` once finished.
+    stopwatch: Option<Stopwatch<C>>,
+}
+
+impl<C: Clock> OwnedStopwatchGuard<C> {
+    /// Stops the stopwatch and returns it.
+    pub fn finish(mut self) -> Stopwatch<C> {
+        let mut stopwatch = self.stopwatch.take().unwrap();
+        stopwatch.stop();
+        stopwatch
+    }
+}
+
+impl<C: Clock> Deref for OwnedStopwatchGuard<C> {
+    type Target = Stopwatch<C>;
+
+    fn deref(&self) -> &Stopwatch<C> {
+        self.stopwatch.as_ref().unwrap()
+    }
+}
+
+impl<C: Clock> DerefMut for OwnedStopwatchGuard<C> {
+    fn deref_mut(&mut self) -> &mut Stopwatch<C> {
+        self.stopwatch.as_mut().unwrap()
+    }
+}
+
+impl<C: Clock> Drop for OwnedStopwatchGuard<C> {
+    fn drop(&mut self) {
+        if let Some(stopwatch) = &mut self.stopwatch {
+            stopwatch.stop();
+        }
+    }
+}
+
+impl<C: Clock> Stopwatch<C> {
+    /// Starts the stopwatch and returns a guard which stops it when dropped.
+    ///
+    /// If the stopwatch is already running, this creates a new split, just
+    /// like `start`.
+    pub fn scoped(&mut self) -> StopwatchGuard<'_, C> {
+        self.start();
+        StopwatchGuard { stopwatch: self }
+    }
+
+    /// Starts the stopwatch and moves it into a guard which stops it when
+    /// dropped or finished.
+    pub fn into_scoped(mut self) -> OwnedStopwatchGuard<C> {
+        self.start();
+        OwnedStopwatchGuard {
+            stopwatch: Some(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::time::Duration;
+
+    fn fallible(sw: &mut Stopwatch<SharedManualClock>, fail: bool) -> Result<(), ()> {
+        let guard = sw.scoped();
+        assert!(guard.is_running());
+        guard.clock().advance(Duration::from_millis(10));
+        if fail {
+            Err(())?;
+        }
+        guard.clock().advance(Duration::from_millis(10));
+        Ok(())
+    }
+
+    #[test]
+    fn stops_on_every_exit() {
+        let mut sw = Stopwatch::with_clock(SharedManualClock::default());
+        assert!(fallible(&mut sw, true).is_err());
+        assert!(!sw.is_running());
+        assert!(fallible(&mut sw, false).is_ok());
+        assert!(!sw.is_running());
+        assert_eq!(sw.spans().len(), 2);
+        assert_eq!(sw.elapsed(), Duration::from_millis(30));
+    }
+
+    #[test]
+    fn stops_on_panic() {
+        let mut sw = Stopwatch::default();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = sw.scoped();
+            panic!("oops");
+        }));
+        assert!(result.is_err());
+        assert!(!sw.is_running());
+        assert_eq!(sw.spans().len(), 1);
+    }
+
+    #[test]
+    fn owned_guard() {
+        let clock = SharedManualClock::default();
+        let guard = Stopwatch::with_clock(clock.clone()).into_scoped();
+        assert!(guard.is_running());
+        clock.advance(Duration::from_millis(10));
+        let sw = guard.finish();
+        assert!(!sw.is_running());
+        assert_eq!(sw.elapsed(), Duration::from_millis(10));
+    }
+}
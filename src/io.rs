@@ -0,0 +1,307 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::{Clock, StdClock, Stopwatch};
+
+/// A reader measuring the time spent in each call to `read` of another one.
+///
+/// Each call is a time span of an internal stopwatch, and the bytes read by
+/// each call are kept to compute the throughput.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use std::io::Read;
+///
+/// let mut reader = TimedRead::new(&b"hello world"[..]);
+/// let mut text = String::new();
+/// reader.read_to_string(&mut text).unwrap();
+/// assert_eq!(reader.bytes_read(), 11);
+/// println!("{} calls, {:?} bytes/s", reader.bytes_per_call().len(), reader.throughput());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimedRead<R, C: Clock = StdClock> {
+    inner: R,
+    stopwatch: Stopwatch<C>,
+    bytes: u64,
+    // the bytes read by each call, one per time span.
+    calls: Vec<u64>,
+}
+
+impl<R> TimedRead<R> {
+    /// Wraps `inner`, measuring time with `StdClock`.
+    pub fn new(inner: R) -> Self {
+        TimedRead::with_clock(inner, StdClock)
+    }
+}
+
+impl<R, C: Clock> TimedRead<R, C> {
+    /// Wraps `inner`, measuring time with `clock`.
+    pub fn with_clock(inner: R, clock: C) -> Self {
+        TimedRead {
+            inner,
+            stopwatch: Stopwatch::with_clock(clock),
+            bytes: 0,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns the stopwatch holding one time span per call to `read`.
+    pub fn stopwatch(&self) -> &Stopwatch<C> {
+        &self.stopwatch
+    }
+
+    /// Returns the number of bytes read.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the number of bytes read by each call to `read`, in the order
+    /// of the time spans of the stopwatch. Failed calls read 0 bytes.
+    pub fn bytes_per_call(&self) -> &[u64] {
+        &self.calls
+    }
+
+    /// Returns the number of bytes read per second spent in `read`, or
+    /// `None` if no time was spent reading.
+    pub fn throughput(&self) -> Option<f64> {
+        throughput(self.bytes, self.stopwatch.elapsed())
+    }
+
+    /// Returns the throughput of each call to `read`, like `throughput`.
+    pub fn throughput_per_call(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        throughput_per_call(&self.calls, &self.stopwatch)
+    }
+
+    /// Returns a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped reader.
+    ///
+    /// Reads made through it are not measured.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, C: Clock> Read for TimedRead<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        let result = self.stopwatch.record(|| inner.read(buf));
+        let read = result.as_ref().map_or(0, |&n| n as u64);
+        self.bytes += read;
+        self.calls.push(read);
+        result
+    }
+}
+
+/// A writer measuring the time spent in each call to `write` and `flush` of
+/// another one.
+///
+/// Each call is a time span of an internal stopwatch, and the bytes written by
+/// each call are kept to compute the throughput.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use std::io::Write;
+///
+/// let mut writer = TimedWrite::new(Vec::new());
+/// writer.write_all(b"hello world").unwrap();
+/// writer.flush().unwrap();
+/// assert_eq!(writer.bytes_written(), 11);
+/// println!("{:?} bytes/s", writer.throughput());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimedWrite<W, C: Clock = StdClock> {
+    inner: W,
+    stopwatch: Stopwatch<C>,
+    bytes: u64,
+    // the bytes written by each call, one per time span.
+    calls: Vec<u64>,
+}
+
+impl<W> TimedWrite<W> {
+    /// Wraps `inner`, measuring time with `StdClock`.
+    pub fn new(inner: W) -> Self {
+        TimedWrite::with_clock(inner, StdClock)
+    }
+}
+
+impl<W, C: Clock> TimedWrite<W, C> {
+    /// Wraps `inner`, measuring time with `clock`.
+    pub fn with_clock(inner: W, clock: C) -> Self {
+        TimedWrite {
+            inner,
+            stopwatch: Stopwatch::with_clock(clock),
+            bytes: 0,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns the stopwatch holding one time span per call to `write` or
+    /// `flush`.
+    pub fn stopwatch(&self) -> &Stopwatch<C> {
+        &self.stopwatch
+    }
+
+    /// Returns the number of bytes written.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the number of bytes written by each call to `write` or
+    /// `flush`, in the order of the time spans of the stopwatch. Failed calls
+    /// and flushes write 0 bytes.
+    pub fn bytes_per_call(&self) -> &[u64] {
+        &self.calls
+    }
+
+    /// Returns the number of bytes written per second spent in `write` and
+    /// `flush`, or `None` if no time was spent writing.
+    pub fn throughput(&self) -> Option<f64> {
+        throughput(self.bytes, self.stopwatch.elapsed())
+    }
+
+    /// Returns the throughput of each call to `write` or `flush`, like
+    /// `throughput`.
+    pub fn throughput_per_call(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        throughput_per_call(&self.calls, &self.stopwatch)
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped writer.
+    ///
+    /// Writes made through it are not measured.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, C: Clock> Write for TimedWrite<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        let result = self.stopwatch.record(|| inner.write(buf));
+        let written = result.as_ref().map_or(0, |&n| n as u64);
+        self.bytes += written;
+        self.calls.push(written);
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let inner = &mut self.inner;
+        let result = self.stopwatch.record_named("flush", || inner.flush());
+        self.calls.push(0);
+        result
+    }
+}
+
+fn throughput(bytes: u64, elapsed: Duration) -> Option<f64> {
+    (!elapsed.is_zero()).then(|| bytes as f64 / elapsed.as_secs_f64())
+}
+
+fn throughput_per_call<'a, C: Clock>(
+    calls: &'a [u64],
+    stopwatch: &'a Stopwatch<C>,
+) -> impl Iterator<Item = Option<f64>> + 'a {
+    calls
+        .iter()
+        .zip(stopwatch.spans())
+        .map(|(&bytes, span)| throughput(bytes, span.elapsed_with(stopwatch.clock())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::io::{self, Read, Write};
+
+    /// Takes 1ms per call, returning at most 4 bytes.
+    struct Slow<'a> {
+        clock: &'a SharedManualClock,
+        data: Vec<u8>,
+    }
+
+    impl Read for Slow<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.clock.advance(ms(1));
+            let len = buf.len().min(self.data.len()).min(4);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Write for Slow<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.clock.advance(ms(1));
+            let len = buf.len().min(4);
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.clock.advance(ms(2));
+            Err(io::ErrorKind::Other.into())
+        }
+    }
+
+    #[test]
+    fn read() {
+        let clock = SharedManualClock::default();
+        let slow = Slow {
+            clock: &clock,
+            data: b"0123456789".to_vec(),
+        };
+        let mut reader = TimedRead::with_clock(slow, clock.clone());
+        assert_eq!(reader.throughput(), None);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"0123456789");
+        assert_eq!(reader.bytes_read(), 10);
+        // 3 calls reading data and 1 reaching the end.
+        assert_eq!(reader.stopwatch().spans().len(), 4);
+        assert_eq!(reader.bytes_per_call(), [4, 4, 2, 0]);
+        assert_eq!(reader.throughput(), Some(2500.0));
+        let per_call: Vec<_> = reader.throughput_per_call().collect();
+        assert_eq!(
+            per_call,
+            [Some(4000.0), Some(4000.0), Some(2000.0), Some(0.0)]
+        );
+        assert!(reader.into_inner().data.is_empty());
+    }
+
+    #[test]
+    fn write() {
+        let clock = SharedManualClock::default();
+        let slow = Slow {
+            clock: &clock,
+            data: Vec::new(),
+        };
+        let mut writer = TimedWrite::with_clock(slow, clock.clone());
+        writer.write_all(b"01234567").unwrap();
+        assert!(writer.flush().is_err());
+        assert_eq!(writer.get_ref().data, b"01234567");
+        assert_eq!(writer.bytes_written(), 8);
+        assert_eq!(writer.bytes_per_call(), [4, 4, 0]);
+        let per_call: Vec<_> = writer.throughput_per_call().collect();
+        assert_eq!(per_call, [Some(4000.0), Some(4000.0), Some(0.0)]);
+        assert_eq!(writer.stopwatch().elapsed_named("flush"), ms(2));
+        assert_eq!(writer.stopwatch().elapsed(), ms(4));
+        assert_eq!(writer.throughput(), Some(2000.0));
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use crate::{Clock, SpanStats, StdClock, Stopwatch};
+
+/// An iterator measuring the time taken by each call to `next` of another
+/// one.
+///
+/// Created by `IteratorExt::timed`. Each call is a time span of an internal
+/// stopwatch, including the last one, which finds the iterator exhausted.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut items = (0..100).map(|i| i * 2).timed();
+/// let sum: u32 = items.by_ref().sum();
+/// assert_eq!(sum, 9900);
+/// assert!(items.is_exhausted());
+/// let stats = items.stats();
+/// assert_eq!(stats.count, 101); // 100 items, and the end of the iterator.
+/// println!("total {:?}, p99 {:?}", items.elapsed(), stats.p99());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimedIter<I, C: Clock = StdClock> {
+    iter: I,
+    stopwatch: Stopwatch<C>,
+    exhausted: bool,
+}
+
+impl<I, C: Clock> TimedIter<I, C> {
+    /// Returns the stopwatch holding one time span per call to `next`.
+    pub fn stopwatch(&self) -> &Stopwatch<C> {
+        &self.stopwatch
+    }
+
+    /// Returns statistics over the time taken by the calls to `next`.
+    pub fn stats(&self) -> SpanStats {
+        self.stopwatch.stats()
+    }
+
+    /// Returns the total time spent in `next`.
+    pub fn elapsed(&self) -> Duration {
+        self.stopwatch.elapsed()
+    }
+
+    /// Returns whether `next` returned `None`.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Returns the wrapped iterator.
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    /// Returns the stopwatch holding one time span per call to `next`.
+    pub fn into_stopwatch(self) -> Stopwatch<C> {
+        self.stopwatch
+    }
+}
+
+impl<I: Iterator, C: Clock> Iterator for TimedIter<I, C> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let iter = &mut self.iter;
+        let item = self.stopwatch.record(|| iter.next());
+        self.exhausted |= item.is_none();
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Adds `timed` to all iterators.
+pub trait IteratorExt: Iterator + Sized {
+    /// Measures the time taken by each call to `next` of this iterator.
+    fn timed(self) -> TimedIter<Self> {
+        self.timed_with(StdClock)
+    }
+
+    /// Measures the time taken by each call to `next` like `timed`, using
+    /// `clock`.
+    fn timed_with<C: Clock>(self, clock: C) -> TimedIter<Self, C> {
+        TimedIter {
+            iter: self,
+            stopwatch: Stopwatch::with_clock(clock),
+            exhausted: false,
+        }
+    }
+}
+
+impl<I: Iterator> IteratorExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn times_each_item() {
+        let clock = SharedManualClock::default();
+        let mut items = [1, 2, 3]
+            .into_iter()
+            .inspect(|&i| clock.advance(ms(i)))
+            .timed_with(clock.clone());
+        assert_eq!(items.size_hint(), (3, Some(3)));
+        assert_eq!(items.next(), Some(1));
+        assert!(!items.is_exhausted());
+        assert_eq!(items.by_ref().collect::<Vec<_>>(), [2, 3]);
+        assert!(items.is_exhausted());
+
+        let stats = items.stats();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.min, Duration::ZERO);
+        assert_eq!(stats.max, ms(3));
+        assert_eq!(items.elapsed(), ms(6));
+        assert!(!items.stopwatch().is_running());
+        assert_eq!(items.into_stopwatch().spans().len(), 4);
+    }
+}
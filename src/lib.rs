@@ -1,226 +1,164 @@
-use std::default::Default;
-use std::fmt;
-use std::time::{Duration, Instant};
-
-/// A span of time that is started but might not have an end yet.
-#[derive(Clone, Debug)]
-pub struct TimeSpan {
-    /// The instant at which the span started.
-    pub start: Instant,
-    /// The instant at which the span stopped, if any.
-    pub stop: Option<Instant>,
-}
-
-/// Converts a TimeSpan into a Duration.
-impl From<TimeSpan> for Duration {
-    fn from(val: TimeSpan) -> Self {
-        if let Some(stop) = val.stop {
-            stop - val.start
-        } else {
-            val.start.elapsed()
-        }
-    }
+//! A stopwatch library for timing things.
+//!
+//! The `std` feature, enabled by default, provides `Stopwatch` and everything
+//! built on it. Without it, the crate is `no_std` and provides
+//! `FixedStopwatch`, which stores its time spans inline and measures time
+//! with a user-provided `Clock` such as `TickClock`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+mod accumulator;
+mod clock;
+#[cfg(feature = "std")]
+mod countdown;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod export;
+mod fixed;
+#[cfg(feature = "std")]
+mod format;
+#[cfg(feature = "std")]
+mod future;
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(feature = "std")]
+mod iter;
+#[cfg(feature = "std")]
+mod measure;
+#[cfg(feature = "std")]
+mod profiler;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod stopwatch;
+#[cfg(feature = "futures")]
+mod stream;
+#[cfg(feature = "macros")]
+mod timed;
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "tracing")]
+mod tracing_layer;
+
+#[cfg(feature = "std")]
+pub use accumulator::*;
+pub use clock::*;
+#[cfg(feature = "std")]
+pub use countdown::*;
+#[cfg(feature = "std")]
+pub use error::*;
+pub use fixed::*;
+#[cfg(feature = "std")]
+pub use format::*;
+#[cfg(feature = "std")]
+pub use future::*;
+#[cfg(feature = "std")]
+pub use guard::*;
+#[cfg(feature = "std")]
+pub use io::*;
+#[cfg(feature = "std")]
+pub use iter::*;
+#[cfg(feature = "std")]
+pub use measure::*;
+#[cfg(feature = "std")]
+pub use profiler::*;
+#[cfg(feature = "std")]
+pub use registry::*;
+#[cfg(feature = "serde")]
+pub use serialization::*;
+#[cfg(feature = "std")]
+pub use shared::*;
+#[cfg(feature = "std")]
+pub use stats::*;
+#[cfg(feature = "std")]
+pub use stopwatch::*;
+#[cfg(feature = "futures")]
+pub use stream::*;
+#[cfg(feature = "macros")]
+pub use timed::*;
+#[cfg(feature = "std")]
+pub use trace::*;
+#[cfg(feature = "tracing")]
+pub use tracing_layer::*;
+
+/// What a stopwatch did when asked to change its state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transition {
+    /// The stopwatch was stopped and started a new time span.
+    Started,
+    /// The stopwatch was running and stopped its time span.
+    Stopped,
+    /// The stopwatch was running, stopped its time span and started a new one.
+    Split,
+    /// The stopwatch was already in the requested state, nothing changed.
+    Unchanged,
 }
 
-/// A stopwatch used to calculate time differences.
-/// # Example
-/// ```rust
-/// use stopwatch2::*;
-///
-/// let mut s = Stopwatch::default();
-/// s.start(); // Starts the stopwatch.
-/// s.start(); // Creates a new time span, which are commonly called "splits".
-/// s.stop(); // Stops the stopwatch.
-/// println!("{}", s); // Prints the total time.
-/// println!("{:?}", s); // Prints the different time spans as debug information.
-/// let total_time = s.elapsed(); // returns the total time as a Duration.
-/// for span in &s.spans {
-///     println!("{:?} -> {:?}", span.start, span.stop);
-/// }
-/// s.spans.clear(); // Reset the stopwatch.
-/// println!("{}", s); // Prints the total time.
-/// println!("{:?}", s); // Prints the different time spans as debug information.
-/// ```
-#[derive(Clone, Default, Debug)]
-pub struct Stopwatch {
-    /// All the time spans that this stopwatch has been or is still running.
-    /// Only the last timespan is allowed to have no stop value, which means it
-    /// is still active.
-    pub spans: Vec<TimeSpan>,
+/// Converts a duration to nanoseconds, saturating at `u64::MAX`.
+#[cfg(feature = "std")]
+pub(crate) fn to_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
 }
 
-/// Prints the total time this Stopwatch has run.
-impl fmt::Display for Stopwatch {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}s", self.elapsed().as_secs_f64())
-    }
-}
-
-impl Stopwatch {
-    /// Starts the stopwatch.
-    ///
-    /// If it is already started, it will create a new split.
-    /// This means it will stop and start the stopwatch, creating a new TimeSpan
-    /// in the process.
-    pub fn start(&mut self) -> Option<TimeSpan> {
-        // if no split or last split is stopped, create new one.
-        let ret = self.stop();
-        self.spans.push(TimeSpan {
-            start: Instant::now(),
-            stop: None,
-        });
-        ret
-    }
-
-    /// Stops the stopwatch without resetting it.
-    pub fn stop(&mut self) -> Option<TimeSpan> {
-        let mut ret = None;
-        if self.is_running() {
-            self.spans.last_mut().unwrap().stop = Some(Instant::now());
-            ret = Some(self.spans.last().unwrap().clone());
+/// Writes `s` as a JSON string, quoted and escaped.
+#[cfg(feature = "std")]
+pub(crate) fn write_json_str(f: &mut impl core::fmt::Write, s: &str) -> core::fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
         }
-        ret
-    }
-
-    /// Returns whether the stopwatch is running.
-    pub fn is_running(&self) -> bool {
-        // if no spans or last span has an end, we are not running.
-        // equiv: if we have splits and the last one has no stop
-        !self.spans.is_empty() && self.spans.last().unwrap().stop.is_none()
     }
+    f.write_char('"')
+}
 
-    /// Returns the total elapsed time accumulated inside of this stopwatch.
-    pub fn elapsed(&self) -> Duration {
-        // better way to do the conversion here?
-        self.spans
-            .iter()
-            .map(|s| {
-                let d: Duration = s.clone().into();
-                d
-            })
-            .sum()
-    }
+/// Converts nanoseconds to a duration, saturating at `Duration::MAX`.
+pub(crate) fn duration_from_nanos(nanos: u128) -> Duration {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).unwrap_or(u64::MAX);
+    Duration::new(secs, (nanos % NANOS_PER_SEC) as u32)
 }
 
+/// Helpers shared by the tests of the modules.
 #[cfg(test)]
-mod tests {
-    use crate::*;
-    use std::time::Duration;
-
-    static SLEEP_MS: u64 = 50;
-    static TOLERANCE_PERCENTAGE: f64 = 0.3;
-
-    #[test]
-    fn repeated_stops() {
-        let mut sw = Stopwatch::default();
-        for _ in 0..1000 {
-            sw.start();
+mod test_util {
+    use core::time::Duration;
+
+    pub(crate) fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Polls `future` to completion, running `between` between polls.
+    #[cfg(feature = "std")]
+    pub(crate) fn block_on<F: core::future::Future>(
+        future: F,
+        mut between: impl FnMut(),
+    ) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut cx = core::task::Context::from_waker(core::task::Waker::noop());
+        loop {
+            if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            between();
         }
-        sw.stop();
-        assert_eq!(sw.spans.len(), 1000);
-        assert!(sw.spans.last().unwrap().stop.is_some());
-    }
-
-    #[test]
-    fn elapsed_none() {
-        let mut sw = Stopwatch::default();
-        sw.stop();
-        sw.stop();
-        assert_eq!(sw.elapsed().as_secs_f32(), 0.0);
-    }
-
-    #[test]
-    fn elapsed_ms() {
-        let mut sw = Stopwatch::default();
-        sw.start();
-        sleep_ms(SLEEP_MS);
-        assert_duration_near(sw.elapsed(), SLEEP_MS);
-    }
-
-    #[test]
-    fn stop() {
-        let mut sw = Stopwatch::default();
-        sw.start();
-        sleep_ms(SLEEP_MS);
-        sw.stop();
-        assert_duration_near(sw.elapsed(), SLEEP_MS);
-        sleep_ms(SLEEP_MS);
-        assert_duration_near(sw.elapsed(), SLEEP_MS);
-    }
-
-    #[test]
-    fn resume_once() {
-        let mut sw = Stopwatch::default();
-        assert_eq!(sw.spans.len(), 0);
-        sw.start();
-        assert_eq!(sw.spans.len(), 1);
-        sleep_ms(SLEEP_MS);
-        sw.stop();
-        assert_eq!(sw.spans.len(), 1);
-        assert_duration_near(sw.elapsed(), SLEEP_MS);
-        sw.start();
-        assert_eq!(sw.spans.len(), 2);
-        sleep_ms(SLEEP_MS);
-        assert_duration_near(sw.elapsed(), 2 * SLEEP_MS);
-    }
-
-    #[test]
-    fn resume_twice() {
-        let mut sw = Stopwatch::default();
-        assert_eq!(sw.spans.len(), 0);
-        sw.start();
-        sleep_ms(SLEEP_MS);
-        sw.stop();
-        assert_eq!(sw.spans.len(), 1);
-        assert_duration_near(sw.elapsed(), SLEEP_MS);
-        sw.start();
-        assert_eq!(sw.spans.len(), 2);
-        sleep_ms(SLEEP_MS);
-        sw.start();
-        assert_eq!(sw.spans.len(), 3);
-        assert_duration_near(sw.elapsed(), 2 * SLEEP_MS);
-        sw.start();
-        assert_eq!(sw.spans.len(), 4);
-        sleep_ms(SLEEP_MS);
-        assert_duration_near(sw.elapsed(), 3 * SLEEP_MS);
-    }
-
-    #[test]
-    fn is_running() {
-        let mut sw = Stopwatch::default();
-        assert!(!sw.is_running());
-        sw.start();
-        assert!(sw.is_running());
-        sw.stop();
-        assert!(!sw.is_running());
-    }
-
-    #[test]
-    fn reset() {
-        let mut sw = Stopwatch::default();
-        sw.start();
-        sleep_ms(SLEEP_MS);
-        sw.spans.clear();
-        assert!(!sw.is_running());
-        sw.start();
-        sleep_ms(SLEEP_MS);
-        assert_duration_near(sw.elapsed(), SLEEP_MS);
-    }
-
-    // helpers
-    fn sleep_ms(ms: u64) {
-        std::thread::sleep(Duration::from_millis(ms))
-    }
-
-    fn assert_near(x: i64, y: i64, tolerance: u64) {
-        let diff = (x - y).unsigned_abs();
-        if diff > tolerance {
-            panic!("Expected {x:?}, got {y:?}");
-        }
-    }
-
-    fn assert_duration_near(duration: Duration, elapsed: u64) {
-        let tolerance_value = (TOLERANCE_PERCENTAGE * elapsed as f64) as u64;
-        assert_near(elapsed as i64, duration.as_millis() as i64, tolerance_value);
     }
 }
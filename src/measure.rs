@@ -0,0 +1,156 @@
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+
+use crate::{Clock, FormatDuration, FormatOptions, Stopwatch};
+
+/// Runs `f` and returns its result along with the time it took.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let (sum, elapsed) = measure(|| (1..=100).sum::<u32>());
+/// assert_eq!(sum, 5050);
+/// println!("{:?}", elapsed);
+/// ```
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let value = f();
+    (value, start.elapsed())
+}
+
+/// Runs `f`, prints the time it took to stderr as `label: time`, and returns
+/// its result.
+pub fn measure_logged<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    let (value, elapsed) = measure(f);
+    log_elapsed(label, elapsed);
+    value
+}
+
+/// Prints `elapsed` to stderr as `label: time`.
+#[doc(hidden)]
+pub fn log_elapsed(label: &str, elapsed: Duration) {
+    let options = FormatOptions::auto().precision(3);
+    eprintln!("{}: {}", label, elapsed.display_with(options));
+}
+
+/// Measures the time taken to evaluate an expression.
+///
+/// `time!(expr)` returns the value of `expr` along with the time it took,
+/// like `measure`. `time!(label, expr)` prints the time to stderr as
+/// `label: time`, like `measure_logged`, and returns the value.
+///
+/// Unlike with `measure`, the expression may use `?`, `return` or `.await`.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let (sum, elapsed) = time!((1..=100).sum::<u32>());
+/// assert_eq!(sum, 5050);
+/// let product = time!("product", (1..=10).product::<u32>()); // Prints "product: 1.234µs".
+/// assert_eq!(product, 3628800);
+/// ```
+#[macro_export]
+macro_rules! time {
+    ($label:expr, $e:expr $(,)?) => {{
+        let start = ::std::time::Instant::now();
+        let value = $e;
+        $crate::log_elapsed($label, start.elapsed());
+        value
+    }};
+    ($e:expr $(,)?) => {{
+        let start = ::std::time::Instant::now();
+        let value = $e;
+        (value, start.elapsed())
+    }};
+}
+
+impl<C: Clock> Stopwatch<C> {
+    /// Runs `f` and adds the time it took as a stopped time span, before the
+    /// running one if any. Returns the result of `f`.
+    pub fn record<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        self.record_span(None, f).0
+    }
+
+    /// Runs `f` and adds the time it took like `record`, labelling the time
+    /// span with `name`.
+    pub fn record_named<T>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        self.record_span(Some(name.into()), f).0
+    }
+
+    /// Runs `f` and adds the time it took like `record_named`, and prints it
+    /// to stderr as `name: time`.
+    pub fn record_logged<T>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let name = name.into();
+        let (value, elapsed) = self.record_span(Some(name.clone()), f);
+        log_elapsed(&name, elapsed);
+        value
+    }
+
+    fn record_span<T>(
+        &mut self,
+        label: Option<Cow<'static, str>>,
+        f: impl FnOnce() -> T,
+    ) -> (T, Duration) {
+        let start = self.clock().now();
+        let value = f();
+        let stop = self.clock().now();
+        self.record_stopped(start, stop, label);
+        (value, self.clock().duration_between(start, stop))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn measures() {
+        let (value, elapsed) = measure(|| {
+            std::thread::sleep(ms(1));
+            42
+        });
+        assert_eq!(value, 42);
+        assert!(elapsed >= ms(1));
+        assert_eq!(measure_logged("measure_logged", || 1), 1);
+
+        fn parse(input: &str) -> Result<(u32, Duration), std::num::ParseIntError> {
+            Ok(time!(input.parse::<u32>()?))
+        }
+        assert_eq!(parse("7").unwrap().0, 7);
+        assert!(parse("x").is_err());
+        assert_eq!(time!("time", 1 + 1), 2);
+    }
+
+    #[test]
+    fn records() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        assert_eq!(sw.record(|| clock.advance(ms(10))), ());
+        sw.start();
+        clock.advance(ms(1));
+        let value = sw.record_named("inner", || {
+            clock.advance(ms(5));
+            "done"
+        });
+        assert_eq!(value, "done");
+        assert_eq!(sw.record_logged("logged", || 3), 3);
+
+        assert!(sw.is_running());
+        assert_eq!(sw.spans().len(), 4);
+        assert_eq!(sw.elapsed_named("inner"), ms(5));
+        assert!(sw.span_named("logged").is_some());
+        assert_eq!(sw.spans()[0].elapsed_with(&clock), ms(10));
+        // the running span is kept last.
+        assert_eq!(sw.spans()[3].stop, None);
+    }
+}
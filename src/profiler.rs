@@ -0,0 +1,373 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+use crate::registry::DEFAULT_HISTORY_LIMIT;
+use crate::{Clock, FormatDuration, FormatOptions, StdClock, Stopwatch};
+
+/// A hierarchical profiler timing nested, named sections of code.
+///
+/// Each section is a node of a tree, keyed by its name and the sections it
+/// was entered in, and timed by its own `Stopwatch`. These stopwatches keep
+/// the last 1000 calls, and older ones only count towards the totals, see
+/// `Stopwatch::set_history_limit`.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut p = Profiler::default();
+/// p.enter("request");
+/// {
+///     let mut load = p.scope("load"); // Exits "load" at the end of the scope.
+///     load.enter("parse");
+///     load.exit();
+/// }
+/// p.exit();
+/// let request = p.node(&["request"]).unwrap();
+/// assert_eq!(request.calls(), 1);
+/// assert!(request.self_time() <= request.total());
+/// println!("{}", p); // Prints the tree of sections with their times.
+/// ```
+#[derive(Clone, Debug)]
+pub struct Profiler<C: Clock = StdClock> {
+    clock: C,
+    nodes: Vec<Node<C>>,
+    roots: Vec<usize>,
+    // the sections currently entered, innermost last.
+    stack: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Node<C: Clock> {
+    name: Cow<'static, str>,
+    children: Vec<usize>,
+    stopwatch: Stopwatch<C>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::with_clock(StdClock)
+    }
+}
+
+impl<C: Clock + Clone> Profiler<C> {
+    /// Creates a profiler which measures time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        Profiler {
+            clock,
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Enters the section `name`, nested in the current one if any.
+    pub fn enter(&mut self, name: impl Into<Cow<'static, str>>) {
+        let name = name.into();
+        let siblings = match self.stack.last() {
+            Some(&parent) => &self.nodes[parent].children,
+            None => &self.roots,
+        };
+        let index = match siblings.iter().find(|&&i| self.nodes[i].name == name) {
+            Some(&index) => index,
+            None => {
+                let index = self.nodes.len();
+                let mut stopwatch = Stopwatch::with_clock(self.clock.clone());
+                stopwatch.set_history_limit(Some(DEFAULT_HISTORY_LIMIT));
+                self.nodes.push(Node {
+                    name,
+                    children: Vec::new(),
+                    stopwatch,
+                });
+                match self.stack.last() {
+                    Some(&parent) => self.nodes[parent].children.push(index),
+                    None => self.roots.push(index),
+                }
+                index
+            }
+        };
+        self.nodes[index].stopwatch.start();
+        self.stack.push(index);
+    }
+
+    /// Exits the current section and returns the time spent in it, or `None`
+    /// if no section is entered.
+    pub fn exit(&mut self) -> Option<Duration> {
+        let index = self.stack.pop()?;
+        let span = self.nodes[index].stopwatch.stop()?;
+        Some(span.elapsed_with(&self.clock))
+    }
+
+    /// Enters the section `name` and returns a guard which exits it when
+    /// dropped.
+    pub fn scope(&mut self, name: impl Into<Cow<'static, str>>) -> ProfilerGuard<'_, C> {
+        self.enter(name);
+        ProfilerGuard {
+            depth: self.stack.len(),
+            profiler: self,
+        }
+    }
+}
+
+impl<C: Clock> Profiler<C> {
+    /// Returns the sections entered outside of any other.
+    pub fn roots(&self) -> impl Iterator<Item = ProfileNode<'_, C>> {
+        self.roots.iter().map(move |&index| ProfileNode {
+            profiler: self,
+            index,
+        })
+    }
+
+    /// Returns the section at `path`, which lists the names of the sections
+    /// from the outermost to the wanted one.
+    pub fn node(&self, path: &[&str]) -> Option<ProfileNode<'_, C>> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots().find(|n| n.name() == *first)?;
+        for name in rest {
+            node = node.children().find(|n| n.name() == *name)?;
+        }
+        Some(node)
+    }
+
+    /// Returns the number of sections currently entered.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Removes all the sections.
+    pub fn reset(&mut self) {
+        self.nodes.clear();
+        self.roots.clear();
+        self.stack.clear();
+    }
+
+    /// Returns a textual report of the tree of sections.
+    pub fn report(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Prints the tree of sections, one per line, indented by depth.
+impl<C: Clock> fmt::Display for Profiler<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_node<C: Clock>(
+            f: &mut fmt::Formatter,
+            node: ProfileNode<'_, C>,
+            depth: usize,
+        ) -> fmt::Result {
+            let options = FormatOptions::auto().precision(3);
+            writeln!(
+                f,
+                "{:indent$}{}: total {}, self {}, {} calls",
+                "",
+                node.name(),
+                node.total().display_with(options),
+                node.self_time().display_with(options),
+                node.calls(),
+                indent = depth * 2
+            )?;
+            for child in node.children() {
+                write_node(f, child, depth + 1)?;
+            }
+            Ok(())
+        }
+
+        for root in self.roots() {
+            write_node(f, root, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// A section of a `Profiler`.
+#[derive(Debug)]
+pub struct ProfileNode<'a, C: Clock = StdClock> {
+    profiler: &'a Profiler<C>,
+    index: usize,
+}
+
+impl<C: Clock> Clone for ProfileNode<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: Clock> Copy for ProfileNode<'_, C> {}
+
+impl<'a, C: Clock> ProfileNode<'a, C> {
+    /// Returns the name of the section.
+    pub fn name(&self) -> &'a str {
+        &self.node().name
+    }
+
+    /// Returns the number of times the section was entered.
+    pub fn calls(&self) -> usize {
+        let stopwatch = self.stopwatch();
+        stopwatch.evicted_count() + stopwatch.spans().len()
+    }
+
+    /// Returns the total time spent in the section, including its children.
+    pub fn total(&self) -> Duration {
+        self.stopwatch().elapsed()
+    }
+
+    /// Returns the time spent in the section outside of its children.
+    pub fn self_time(&self) -> Duration {
+        self.children().fold(self.total(), |time, child| {
+            time.saturating_sub(child.total())
+        })
+    }
+
+    /// Returns the sections entered inside of this one.
+    pub fn children(&self) -> impl Iterator<Item = ProfileNode<'a, C>> {
+        let profiler = self.profiler;
+        self.node()
+            .children
+            .iter()
+            .map(move |&index| ProfileNode { profiler, index })
+    }
+
+    /// Returns the stopwatch timing the section, with one time span per call
+    /// among the last 1000.
+    pub fn stopwatch(&self) -> &'a Stopwatch<C> {
+        &self.node().stopwatch
+    }
+
+    fn node(&self) -> &'a Node<C> {
+        &self.profiler.nodes[self.index]
+    }
+}
+
+/// Exits a section of a `Profiler` when dropped.
+///
+/// Created by `Profiler::scope`. Nested sections are entered through the
+/// guard, which dereferences to the profiler. When dropped, it also exits the
+/// nested sections which are still entered, such as after an early return.
+#[derive(Debug)]
+pub struct ProfilerGuard<'a, C: Clock + Clone = StdClock> {
+    profiler: &'a mut Profiler<C>,
+    // the depth of the profiler with the section of the guard entered.
+    depth: usize,
+}
+
+impl<C: Clock + Clone> Deref for ProfilerGuard<'_, C> {
+    type Target = Profiler<C>;
+
+    fn deref(&self) -> &Profiler<C> {
+        self.profiler
+    }
+}
+
+impl<C: Clock + Clone> DerefMut for ProfilerGuard<'_, C> {
+    fn deref_mut(&mut self) -> &mut Profiler<C> {
+        self.profiler
+    }
+}
+
+impl<C: Clock + Clone> Drop for ProfilerGuard<'_, C> {
+    fn drop(&mut self) {
+        while self.profiler.depth() >= self.depth {
+            self.profiler.exit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+
+    #[test]
+    fn tree() {
+        let clock = SharedManualClock::default();
+        let mut p = Profiler::with_clock(clock.clone());
+        p.enter("request");
+        clock.advance(ms(5));
+        for _ in 0..2 {
+            let mut load = p.scope("load");
+            clock.advance(ms(10));
+            load.enter("parse");
+            clock.advance(ms(20));
+            assert_eq!(load.depth(), 3);
+            assert_eq!(load.exit(), Some(ms(20)));
+        }
+        p.enter("request");
+        clock.advance(ms(1));
+        p.exit();
+        assert_eq!(p.exit(), Some(ms(66)));
+        assert_eq!(p.exit(), None);
+
+        let request = p.node(&["request"]).unwrap();
+        assert_eq!(request.calls(), 1);
+        assert_eq!(request.total(), ms(66));
+        assert_eq!(request.self_time(), ms(5));
+        let load = p.node(&["request", "load"]).unwrap();
+        assert_eq!(load.calls(), 2);
+        assert_eq!(load.total(), ms(60));
+        assert_eq!(load.self_time(), ms(20));
+        let parse = p.node(&["request", "load", "parse"]).unwrap();
+        assert_eq!(parse.total(), ms(40));
+        assert_eq!(parse.children().count(), 0);
+        assert!(p.node(&["load"]).is_none());
+        assert_eq!(p.node(&["request", "request"]).unwrap().total(), ms(1));
+
+        assert_eq!(
+            p.report(),
+            "request: total 66.000ms, self 5.000ms, 1 calls\n\
+             \x20 load: total 60.000ms, self 20.000ms, 2 calls\n\
+             \x20   parse: total 40.000ms, self 40.000ms, 2 calls\n\
+             \x20 request: total 1.000ms, self 1.000ms, 1 calls\n"
+        );
+        p.reset();
+        assert_eq!(p.roots().count(), 0);
+    }
+
+    #[test]
+    fn early_return() {
+        fn load(p: &mut Profiler<SharedManualClock>, fail: bool) -> Result<(), ()> {
+            let clock = p.clock.clone();
+            let mut load = p.scope("load");
+            load.enter("parse");
+            clock.advance(ms(10));
+            if fail {
+                return Err(());
+            }
+            load.exit();
+            Ok(())
+        }
+
+        let clock = SharedManualClock::default();
+        let mut p = Profiler::with_clock(clock.clone());
+        p.enter("request");
+        assert!(load(&mut p, true).is_err());
+        assert_eq!(p.depth(), 1);
+        assert!(!p
+            .node(&["request", "load"])
+            .unwrap()
+            .stopwatch()
+            .is_running());
+        assert_eq!(
+            p.node(&["request", "load", "parse"]).unwrap().total(),
+            ms(10)
+        );
+        load(&mut p, false).unwrap();
+        assert_eq!(p.depth(), 1);
+        assert_eq!(p.node(&["request", "load"]).unwrap().calls(), 2);
+        assert_eq!(p.exit(), Some(ms(20)));
+    }
+
+    #[test]
+    fn hot_loop() {
+        let clock = SharedManualClock::default();
+        let mut p = Profiler::with_clock(clock.clone());
+        for _ in 0..1500 {
+            let _step = p.scope("step");
+            clock.advance(ms(1));
+        }
+        let step = p.node(&["step"]).unwrap();
+        assert_eq!(step.stopwatch().spans().len(), 1000);
+        assert_eq!(step.calls(), 1500);
+        assert_eq!(step.total(), ms(1500));
+    }
+}
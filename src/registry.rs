@@ -0,0 +1,370 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use crate::{Clock, SpanError, StdClock, Stopwatch, TimeSpan};
+
+/// A thread-safe collection of stopwatches identified by name.
+///
+/// Stopwatches are created the first time their name is used. The global
+/// registry is returned by `registry`, and each thread has its own registry
+/// through `with_thread_registry`.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// registry().start("db"); // Starts the "db" stopwatch from anywhere.
+/// // Query the database...
+/// registry().stop("db");
+/// {
+///     let _span = registry().scoped("render"); // Recorded at the end of the scope.
+/// }
+/// for (name, stopwatch) in registry().snapshot() {
+///     println!("{}: {}", name, stopwatch);
+/// }
+/// registry().reset_all();
+/// ```
+#[derive(Debug)]
+pub struct Registry<C: Clock = StdClock> {
+    clock: C,
+    // the history limit of the stopwatches, 0 if none. Only changed while
+    // holding the lock.
+    history_limit: AtomicUsize,
+    stopwatches: Mutex<BTreeMap<Cow<'static, str>, Stopwatch<C>>>,
+}
+
+/// The history limit of the stopwatches which record time spans on their own:
+/// those of the global and thread registries, of `StopwatchLayer`, of
+/// `Profiler` and of `SharedStopwatch`.
+pub(crate) const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::with_clock(StdClock)
+    }
+}
+
+thread_local! {
+    static THREAD_REGISTRY: Arc<Registry> = Arc::new(limited_registry());
+}
+
+/// Returns the global registry, shared by all threads.
+///
+/// Its stopwatches keep their last 1000 time spans, see
+/// `Registry::set_history_limit`.
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(limited_registry)
+}
+
+/// Runs `f` with the registry of the current thread.
+///
+/// Its stopwatches keep their last 1000 time spans, see
+/// `Registry::set_history_limit`.
+pub fn with_thread_registry<R>(f: impl FnOnce(&Registry) -> R) -> R {
+    THREAD_REGISTRY.with(|registry| f(registry))
+}
+
+/// Returns the registry of the current thread, or `None` if it was already
+/// destroyed because the thread is exiting.
+#[cfg(feature = "macros")]
+pub(crate) fn thread_registry() -> Option<Arc<Registry>> {
+    THREAD_REGISTRY.try_with(Arc::clone).ok()
+}
+
+fn limited_registry() -> Registry {
+    let registry = Registry::default();
+    registry.set_history_limit(Some(DEFAULT_HISTORY_LIMIT));
+    registry
+}
+
+impl<C: Clock + Clone> Registry<C> {
+    /// Creates an empty registry whose stopwatches measure time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        Registry {
+            clock,
+            history_limit: AtomicUsize::new(0),
+            stopwatches: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the clock used by the stopwatches of this registry.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns the maximum number of time spans kept by each stopwatch, if
+    /// any.
+    pub fn history_limit(&self) -> Option<usize> {
+        Some(self.history_limit.load(Ordering::Relaxed)).filter(|&limit| limit > 0)
+    }
+
+    /// Sets the history limit of all the stopwatches, including the ones
+    /// created later, see `Stopwatch::set_history_limit`.
+    pub fn set_history_limit(&self, limit: Option<usize>) {
+        let limit = limit.map(|limit| limit.max(1));
+        let mut stopwatches = self.lock();
+        self.history_limit
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+        for stopwatch in stopwatches.values_mut() {
+            stopwatch.set_history_limit(limit);
+        }
+    }
+
+    /// Runs `f` with the stopwatch `name`, creating it if needed.
+    ///
+    /// The registry stays locked while `f` runs, so `f` must not use this
+    /// registry: doing so deadlocks.
+    pub fn with<R>(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        f: impl FnOnce(&mut Stopwatch<C>) -> R,
+    ) -> R {
+        let mut stopwatches = self.lock();
+        let stopwatch = stopwatches.entry(name.into()).or_insert_with(|| {
+            let mut stopwatch = Stopwatch::with_clock(self.clock.clone());
+            stopwatch.set_history_limit(self.history_limit());
+            stopwatch
+        });
+        f(stopwatch)
+    }
+
+    /// Starts the stopwatch `name`, see `Stopwatch::start`.
+    pub fn start(&self, name: impl Into<Cow<'static, str>>) -> Option<TimeSpan<C>> {
+        self.with(name, Stopwatch::start)
+    }
+
+    /// Stops the stopwatch `name`, see `Stopwatch::stop`.
+    pub fn stop(&self, name: &str) -> Option<TimeSpan<C>> {
+        self.lock().get_mut(name)?.stop()
+    }
+
+    /// Adds a stopped time span to the stopwatch `name`.
+    ///
+    /// The time span is inserted after the ones which stopped before it, and
+    /// before the running one if any, so that concurrent measurements never
+    /// conflict.
+    pub fn record(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        span: TimeSpan<C>,
+    ) -> Result<(), SpanError> {
+        self.with(name, |stopwatch| stopwatch.push_stopped_span(span))
+    }
+
+    /// Adds a time span from `start` to `stop` to the stopwatch `name`, see
+    /// `Stopwatch::record_stopped`.
+    pub(crate) fn record_stopped(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        start: C::Instant,
+        stop: C::Instant,
+    ) {
+        self.with(name, |stopwatch| {
+            stopwatch.record_stopped(start, stop, None)
+        });
+    }
+
+    /// Starts measuring a time span which is added to the stopwatch `name`
+    /// when the returned guard is dropped.
+    ///
+    /// Unlike `start`, several time spans of the same stopwatch may be
+    /// measured at once, from any thread.
+    pub fn scoped(&self, name: impl Into<Cow<'static, str>>) -> RegistryGuard<'_, C> {
+        RegistryGuard {
+            registry: self,
+            name: Some(name.into()),
+            start: self.clock.now(),
+        }
+    }
+
+    /// Returns the total time of the stopwatch `name`, if it exists.
+    pub fn elapsed(&self, name: &str) -> Option<Duration> {
+        self.lock().get(name).map(Stopwatch::elapsed)
+    }
+
+    /// Returns a copy of the stopwatch `name`, if it exists.
+    pub fn get(&self, name: &str) -> Option<Stopwatch<C>> {
+        self.lock().get(name).cloned()
+    }
+
+    /// Returns the names of the stopwatches, in order.
+    pub fn names(&self) -> Vec<Cow<'static, str>> {
+        self.lock().keys().cloned().collect()
+    }
+
+    /// Returns a copy of all the stopwatches, by name.
+    pub fn snapshot(&self) -> BTreeMap<Cow<'static, str>, Stopwatch<C>> {
+        self.lock().clone()
+    }
+
+    /// Removes the stopwatch `name` and returns it, if it exists.
+    pub fn remove(&self, name: &str) -> Option<Stopwatch<C>> {
+        self.lock().remove(name)
+    }
+
+    /// Resets the stopwatch `name`, see `Stopwatch::reset`. Returns whether
+    /// it exists.
+    pub fn reset(&self, name: &str) -> bool {
+        self.lock().get_mut(name).map(Stopwatch::reset).is_some()
+    }
+
+    /// Resets all the stopwatches, see `Stopwatch::reset`.
+    pub fn reset_all(&self) {
+        self.lock().values_mut().for_each(Stopwatch::reset);
+    }
+
+    /// Removes all the stopwatches.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<Cow<'static, str>, Stopwatch<C>>> {
+        // a panic while holding the lock cannot leave a stopwatch invalid.
+        self.stopwatches
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Adds a time span to a stopwatch of a `Registry` when dropped.
+///
+/// Created by `Registry::scoped`.
+#[derive(Debug)]
+pub struct RegistryGuard<'a, C: Clock + Clone = StdClock> {
+    registry: &'a Registry<C>,
+    name: Option<Cow<'static, str>>,
+    start: C::Instant,
+}
+
+impl<C: Clock + Clone> RegistryGuard<'_, C> {
+    /// Stops the time span now and returns its duration.
+    pub fn stop(mut self) -> Duration {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Duration {
+        let stop = self.registry.clock.now();
+        let elapsed = self.registry.clock.duration_between(self.start, stop);
+        if let Some(name) = self.name.take() {
+            self.registry.record_stopped(name, self.start, stop);
+        }
+        elapsed
+    }
+}
+
+impl<C: Clock + Clone> Drop for RegistryGuard<'_, C> {
+    fn drop(&mut self) {
+        if self.name.is_some() {
+            self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn named_stopwatches() {
+        let clock = SharedManualClock::default();
+        let r = Registry::with_clock(clock.clone());
+        r.start("db");
+        r.start("render");
+        clock.advance(ms(10));
+        assert!(r.stop("db").is_some());
+        assert!(r.stop("missing").is_none());
+        clock.advance(ms(5));
+        assert_eq!(r.elapsed("db"), Some(ms(10)));
+        assert_eq!(r.elapsed("render"), Some(ms(15)));
+        assert_eq!(r.names(), ["db", "render"]);
+
+        // recorded spans go before the running one.
+        let start = clock.now();
+        clock.advance(ms(1));
+        let span = TimeSpan {
+            start,
+            stop: Some(clock.now()),
+            label: None,
+        };
+        r.record("render", span).unwrap();
+        let render = r.get("render").unwrap();
+        assert!(render.is_running());
+        assert_eq!(render.spans().len(), 2);
+        assert_eq!(render.elapsed(), ms(17));
+
+        assert!(r.reset("db"));
+        assert_eq!(r.elapsed("db"), Some(Duration::ZERO));
+        assert!(r.remove("render").is_some());
+        assert_eq!(r.snapshot().len(), 1);
+        r.start("render");
+        clock.advance(ms(1));
+        r.reset_all();
+        assert_eq!(r.names(), ["db", "render"]);
+        assert_eq!(r.elapsed("render"), Some(Duration::ZERO));
+        assert!(!r.get("render").unwrap().is_running());
+        r.clear();
+        assert!(r.names().is_empty());
+    }
+
+    #[test]
+    fn history_limit() {
+        let clock = SharedManualClock::default();
+        let r = Registry::with_clock(clock.clone());
+        assert_eq!(r.history_limit(), None);
+        for _ in 0..3 {
+            r.scoped("before").stop();
+        }
+        r.set_history_limit(Some(2));
+        assert_eq!(r.history_limit(), Some(2));
+        for _ in 0..3 {
+            clock.advance(ms(1));
+            r.scoped("after").stop();
+        }
+        assert_eq!(r.get("before").unwrap().spans().len(), 2);
+        let after = r.get("after").unwrap();
+        assert_eq!(after.spans().len(), 2);
+        assert_eq!(after.evicted_count(), 1);
+        r.set_history_limit(None);
+        assert_eq!(r.history_limit(), None);
+        assert_eq!(r.get("after").unwrap().history_limit(), None);
+    }
+
+    #[test]
+    fn scoped_across_threads() {
+        let clock = SharedManualClock::default();
+        let r = Registry::with_clock(clock.clone());
+        let guards: Vec<_> = (0..4).map(|_| r.scoped("work")).collect();
+        clock.advance(ms(10));
+        thread::scope(|scope| {
+            for guard in guards {
+                scope.spawn(move || drop(guard));
+            }
+        });
+        assert_eq!(r.scoped("work").stop(), Duration::ZERO);
+        let work = r.get("work").unwrap();
+        assert_eq!(work.spans().len(), 5);
+        assert_eq!(work.elapsed(), ms(40));
+    }
+
+    #[test]
+    fn global_and_thread_scopes() {
+        registry().start("global_and_thread_scopes");
+        with_thread_registry(|r| r.start("local"));
+        assert_eq!(registry().history_limit(), Some(1000));
+        with_thread_registry(|r| assert_eq!(r.history_limit(), Some(1000)));
+        thread::spawn(|| {
+            assert!(registry().get("global_and_thread_scopes").is_some());
+            with_thread_registry(|r| assert!(r.get("local").is_none()));
+        })
+        .join()
+        .unwrap();
+        with_thread_registry(|r| assert!(r.stop("local").is_some()));
+        registry().remove("global_and_thread_scopes");
+    }
+}
@@ -0,0 +1,350 @@
+//! Serialization of stopwatches and time spans, enabled by the `serde` feature.
+//!
+//! `Instant`s cannot be serialized, so time spans are stored as offsets from
+//! an epoch, along with the offset at which they were serialized. When
+//! deserializing, the epoch is placed so that the same amount of time passed
+//! since then, which keeps running time spans running. Deserialize an
+//! `Anchored` value to also get the wall-clock time of the epoch.
+
+use std::borrow::Cow;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{StdClock, Stopwatch, TimeSpan};
+
+#[derive(Serialize, Deserialize)]
+struct StopwatchRecord {
+    /// Time since the UNIX epoch at the epoch of the spans, if known.
+    #[serde(default)]
+    epoch_unix: Option<Duration>,
+    /// Offset of the serialization from the epoch.
+    captured_at: Duration,
+    spans: Vec<SpanRecord>,
+    #[serde(default)]
+    history_limit: Option<usize>,
+    /// Number of spans evicted by the history limit.
+    #[serde(default)]
+    evicted_count: usize,
+    /// Time accumulated by the spans evicted by the history limit.
+    #[serde(default)]
+    evicted_elapsed: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpanRecord {
+    #[serde(default)]
+    label: Option<String>,
+    /// Offset of the start from the epoch.
+    start: Duration,
+    /// Offset of the stop from the epoch, `None` if still running.
+    stop: Option<Duration>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TimeSpanRecord {
+    /// Time since the UNIX epoch at the start of the span, if known.
+    #[serde(default)]
+    epoch_unix: Option<Duration>,
+    /// Offset of the serialization from the start.
+    captured_at: Duration,
+    #[serde(default)]
+    label: Option<String>,
+    /// Offset of the stop from the start, `None` if still running.
+    duration: Option<Duration>,
+}
+
+/// Serializes the time spans as offsets from the earliest start, which is not
+/// necessarily the one of the first time span.
+impl Serialize for Stopwatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let now = Instant::now();
+        let epoch = self.iter().map(|span| span.start).min().unwrap_or(now);
+        let captured_at = now.saturating_duration_since(epoch);
+        StopwatchRecord {
+            epoch_unix: unix_time_before(captured_at),
+            captured_at,
+            spans: self
+                .iter()
+                .map(|span| SpanRecord {
+                    label: span.label.as_deref().map(str::to_owned),
+                    start: span.start.saturating_duration_since(epoch),
+                    stop: span.stop.map(|stop| stop.saturating_duration_since(epoch)),
+                })
+                .collect(),
+            history_limit: self.history_limit(),
+            evicted_count: self.evicted_count(),
+            evicted_elapsed: self.evicted_elapsed(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Stopwatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Anchored::<Stopwatch>::deserialize(deserializer).map(|anchored| anchored.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for Anchored<Stopwatch> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = StopwatchRecord::deserialize(deserializer)?;
+        let epoch = epoch_before(record.captured_at)?;
+        let spans = record
+            .spans
+            .into_iter()
+            .map(|span| {
+                Ok(TimeSpan {
+                    start: instant_after(epoch, span.start)?,
+                    stop: span
+                        .stop
+                        .map(|stop| instant_after(epoch, stop))
+                        .transpose()?,
+                    label: span.label.map(Cow::Owned),
+                })
+            })
+            .collect::<Result<_, D::Error>>()?;
+        let mut stopwatch = Stopwatch::from_parts(
+            StdClock,
+            spans,
+            record.evicted_count,
+            record.evicted_elapsed,
+        )
+        .map_err(D::Error::custom)?;
+        stopwatch.set_history_limit(record.history_limit);
+        Ok(Anchored {
+            value: stopwatch,
+            epoch,
+            epoch_unix: record.epoch_unix,
+        })
+    }
+}
+
+/// Serializes the time span as offsets from its start.
+impl Serialize for TimeSpan {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let captured_at = self.start.elapsed();
+        TimeSpanRecord {
+            epoch_unix: unix_time_before(captured_at),
+            captured_at,
+            label: self.label.as_deref().map(str::to_owned),
+            duration: self
+                .stop
+                .map(|stop| stop.saturating_duration_since(self.start)),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeSpan {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Anchored::<TimeSpan>::deserialize(deserializer).map(|anchored| anchored.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for Anchored<TimeSpan> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = TimeSpanRecord::deserialize(deserializer)?;
+        let start = epoch_before(record.captured_at)?;
+        let stop = record
+            .duration
+            .map(|duration| instant_after(start, duration))
+            .transpose()?;
+        Ok(Anchored {
+            value: TimeSpan {
+                start,
+                stop,
+                label: record.label.map(Cow::Owned),
+            },
+            epoch: start,
+            epoch_unix: record.epoch_unix,
+        })
+    }
+}
+
+/// A deserialized `Stopwatch` or `TimeSpan`, along with the wall-clock time
+/// recorded when it was serialized.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut s = Stopwatch::default();
+/// s.start();
+/// let json = serde_json::to_string(&s).unwrap();
+/// let copy: Anchored<Stopwatch> = serde_json::from_str(&json).unwrap();
+/// let started = copy.wall_clock(copy.value.spans()[0].start); // The SystemTime of the start.
+/// println!("started at {:?}", started);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Anchored<T> {
+    /// The deserialized value.
+    pub value: T,
+    epoch: Instant,
+    epoch_unix: Option<Duration>,
+}
+
+impl<T> Anchored<T> {
+    /// Returns the wall-clock time at `instant`, an instant of the time spans
+    /// of the value, or `None` if it was not recorded when serializing.
+    pub fn wall_clock(&self, instant: Instant) -> Option<SystemTime> {
+        let epoch = UNIX_EPOCH.checked_add(self.epoch_unix?)?;
+        if instant >= self.epoch {
+            epoch.checked_add(instant - self.epoch)
+        } else {
+            epoch.checked_sub(self.epoch - instant)
+        }
+    }
+}
+
+/// Returns the time since the UNIX epoch at `ago` before now.
+fn unix_time_before(ago: Duration) -> Option<Duration> {
+    SystemTime::now()
+        .checked_sub(ago)?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+}
+
+/// Returns the instant at `offset` after `epoch`.
+fn instant_after<E: Error>(epoch: Instant, offset: Duration) -> Result<Instant, E> {
+    epoch
+        .checked_add(offset)
+        .ok_or_else(|| E::custom("a time span cannot be represented by an Instant"))
+}
+
+/// Returns the instant at `ago` before now.
+fn epoch_before<E: Error>(ago: Duration) -> Result<Instant, E> {
+    Instant::now()
+        .checked_sub(ago)
+        .ok_or_else(|| E::custom("the epoch cannot be represented by an Instant"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    #[test]
+    fn stopwatch_round_trip() {
+        let mut sw = Stopwatch::default();
+        sw.start_named("first");
+        std::thread::sleep(Duration::from_millis(5));
+        sw.start();
+        sw.stop();
+        sw.start();
+
+        let json = serde_json::to_string(&sw).unwrap();
+        let copy: Stopwatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.spans().len(), 3);
+        assert!(copy.is_running());
+        assert_eq!(copy.spans()[0].label.as_deref(), Some("first"));
+        for (span, copied) in sw.spans().iter().zip(copy.spans()).take(2) {
+            assert_eq!(Duration::from(span.clone()), Duration::from(copied.clone()));
+        }
+        assert!(copy.elapsed() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn unordered_starts_round_trip() {
+        let epoch = Instant::now();
+        let at = |ms| epoch + Duration::from_millis(ms);
+        let mut sw = Stopwatch::default();
+        for (start, stop) in [(10, 20), (0, 30)] {
+            sw.push_span(TimeSpan {
+                start: at(start),
+                stop: Some(at(stop)),
+                label: None,
+            })
+            .unwrap();
+        }
+
+        let copy: Stopwatch = serde_json::from_str(&serde_json::to_string(&sw).unwrap()).unwrap();
+        let spans = copy.spans();
+        assert_eq!(spans[0].start - spans[1].start, Duration::from_millis(10));
+        assert_eq!(Duration::from(spans[0].clone()), Duration::from_millis(10));
+        assert_eq!(Duration::from(spans[1].clone()), Duration::from_millis(30));
+        assert_eq!(copy.elapsed(), Duration::from_millis(40));
+    }
+
+    #[test]
+    fn stopwatch_format() {
+        let json = r#"{
+            "captured_at": {"secs": 3, "nanos": 0},
+            "spans": [
+                {"start": {"secs": 0, "nanos": 0}, "stop": {"secs": 1, "nanos": 0}},
+                {"label": "b", "start": {"secs": 2, "nanos": 0}, "stop": null}
+            ]
+        }"#;
+        let sw: Stopwatch = serde_json::from_str(json).unwrap();
+        assert!(sw.is_running());
+        assert_eq!(sw.spans()[1].label.as_deref(), Some("b"));
+        assert!(sw.elapsed() >= Duration::from_secs(2));
+
+        let invalid = json.replace("null", r#"{"secs": 1, "nanos": 0}"#);
+        assert!(serde_json::from_str::<Stopwatch>(&invalid).is_err());
+
+        let value = serde_json::to_value(&sw).unwrap();
+        assert!(value["epoch_unix"].is_object());
+        assert_eq!(value["spans"][0]["stop"]["secs"], 1);
+        assert_eq!(value["spans"][1]["stop"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn history_round_trip() {
+        let mut sw = Stopwatch::default();
+        sw.set_history_limit(Some(1));
+        sw.start();
+        std::thread::sleep(Duration::from_millis(5));
+        sw.start();
+        sw.stop();
+
+        let copy: Stopwatch = serde_json::from_str(&serde_json::to_string(&sw).unwrap()).unwrap();
+        assert_eq!(copy.spans().len(), 1);
+        assert_eq!(copy.history_limit(), Some(1));
+        assert_eq!(copy.evicted_count(), 1);
+        assert_eq!(copy.evicted_elapsed(), sw.evicted_elapsed());
+        assert!(copy.elapsed() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let json = r#"{
+            "captured_at": {"secs": 0, "nanos": 0},
+            "spans": [{"start": {"secs": 18446744073709551615, "nanos": 0}, "stop": null}]
+        }"#;
+        assert!(serde_json::from_str::<Stopwatch>(json).is_err());
+        let json = r#"{
+            "captured_at": {"secs": 0, "nanos": 0},
+            "duration": {"secs": 18446744073709551615, "nanos": 0}
+        }"#;
+        assert!(serde_json::from_str::<TimeSpan>(json).is_err());
+    }
+
+    #[test]
+    fn wall_clock() {
+        let json = r#"{
+            "epoch_unix": {"secs": 1000, "nanos": 0},
+            "captured_at": {"secs": 3, "nanos": 0},
+            "spans": [{"start": {"secs": 2, "nanos": 0}, "stop": null}]
+        }"#;
+        let sw: Anchored<Stopwatch> = serde_json::from_str(json).unwrap();
+        let start = sw.wall_clock(sw.value.spans()[0].start).unwrap();
+        assert_eq!(start, UNIX_EPOCH + Duration::from_secs(1002));
+
+        let json = r#"{"captured_at": {"secs": 1, "nanos": 0}, "duration": null}"#;
+        let span: Anchored<TimeSpan> = serde_json::from_str(json).unwrap();
+        assert!(span.value.stop.is_none());
+        assert_eq!(span.wall_clock(span.value.start), None);
+    }
+
+    #[test]
+    fn time_span_round_trip() {
+        let mut sw = Stopwatch::default();
+        sw.start_named("span");
+        let span = sw.stop().unwrap();
+        let json = serde_json::to_string(&span).unwrap();
+        let copy: TimeSpan = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.label.as_deref(), Some("span"));
+        assert_eq!(Duration::from(span), Duration::from(copy));
+    }
+}
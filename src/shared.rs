@@ -0,0 +1,492 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::registry::DEFAULT_HISTORY_LIMIT;
+use crate::{duration_from_nanos, to_nanos, Clock, SpanError, StdClock, Stopwatch, TimeSpan};
+
+/// The number of shards the time spans are split into, so that threads
+/// starting and stopping time spans rarely wait for each other.
+const SHARDS: usize = 16;
+
+thread_local! {
+    static SHARD: usize = {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed) % SHARDS
+    };
+}
+
+/// A stopwatch which can accumulate time spans from several threads at once.
+///
+/// Each call to `start` opens an independent time span, which is stopped when
+/// the returned guard is dropped. Threads rarely wait for each other to start
+/// or stop time spans, and reading the total time with `elapsed` never blocks
+/// them.
+///
+/// The last 1000 stopped time spans are kept for `snapshot`, see
+/// `set_history_limit`.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use std::thread;
+///
+/// let s = SharedStopwatch::default();
+/// thread::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| {
+///             let _span = s.start(); // Stopped at the end of the scope.
+///             // Do some work...
+///         });
+///     }
+/// });
+/// println!("{:?}", s.elapsed()); // Prints the time accumulated by all threads.
+/// let s: Stopwatch = s.snapshot(); // Copies the time spans into a plain stopwatch.
+/// assert_eq!(s.spans().len(), 4);
+/// ```
+#[derive(Debug)]
+pub struct SharedStopwatch<C: Clock = StdClock> {
+    clock: C,
+    epoch: C::Instant,
+    // numbers of updates of the counters below which began and ended, see
+    // `elapsed`.
+    updates_begun: AtomicU64,
+    updates_ended: AtomicU64,
+    // saturates at `u64::MAX`.
+    completed_nanos: AtomicU64,
+    running_count: AtomicU64,
+    // sum of the offsets from `epoch` at which the running spans started,
+    // wrapping on overflow.
+    running_start_nanos: AtomicU64,
+    // the history limit of each shard, 0 if none.
+    history_limit: AtomicUsize,
+    shards: Box<[Mutex<Shard<C>>]>,
+}
+
+/// The time spans started by some of the threads.
+#[derive(Debug)]
+struct Shard<C: Clock> {
+    // in the order in which they were stopped or added.
+    spans: VecDeque<TimeSpan<C>>,
+    // indexed by the slot of their guard, `None` if the slot is free.
+    running: Vec<Option<TimeSpan<C>>>,
+    free_slots: Vec<usize>,
+    evicted_count: usize,
+    evicted_elapsed: Duration,
+}
+
+impl<C: Clock> Shard<C> {
+    fn evict(&mut self, limit: usize, clock: &C) {
+        while limit > 0 && self.spans.len() > limit {
+            let span = self.spans.pop_front().unwrap();
+            self.evicted_count += 1;
+            self.evicted_elapsed = self
+                .evicted_elapsed
+                .saturating_add(span.elapsed_with(clock));
+        }
+    }
+}
+
+impl Default for SharedStopwatch {
+    fn default() -> Self {
+        SharedStopwatch::with_clock(StdClock)
+    }
+}
+
+impl<C: Clock> SharedStopwatch<C> {
+    /// Creates a shared stopwatch which measures time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        SharedStopwatch {
+            epoch: clock.now(),
+            clock,
+            updates_begun: AtomicU64::new(0),
+            updates_ended: AtomicU64::new(0),
+            completed_nanos: AtomicU64::new(0),
+            running_count: AtomicU64::new(0),
+            running_start_nanos: AtomicU64::new(0),
+            history_limit: AtomicUsize::new(DEFAULT_HISTORY_LIMIT),
+            shards: (0..SHARDS)
+                .map(|_| {
+                    Mutex::new(Shard {
+                        spans: VecDeque::new(),
+                        running: Vec::new(),
+                        free_slots: Vec::new(),
+                        evicted_count: 0,
+                        evicted_elapsed: Duration::ZERO,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the clock used by this stopwatch.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns the maximum number of stopped time spans kept, if any.
+    pub fn history_limit(&self) -> Option<usize> {
+        Some(self.history_limit.load(Ordering::Relaxed)).filter(|&limit| limit > 0)
+    }
+
+    /// Sets the maximum number of stopped time spans kept, or `None` to keep
+    /// all of them.
+    ///
+    /// Once the limit is reached, the oldest time spans are evicted. Evicted
+    /// time spans still count towards `elapsed` and the `elapsed` of
+    /// `snapshot`, but are not part of its time spans. The limit is applied
+    /// to each group of threads sharing a shard, so up to 16 times as many
+    /// time spans may be stored until `snapshot` evicts them. A limit of zero
+    /// is treated as one.
+    pub fn set_history_limit(&self, limit: Option<usize>) {
+        let limit = limit.map_or(0, |limit| limit.max(1));
+        self.history_limit.store(limit, Ordering::Relaxed);
+        for shard in 0..SHARDS {
+            self.lock(shard).evict(limit, &self.clock);
+        }
+    }
+
+    /// Starts a new time span, which is stopped when the returned guard is
+    /// dropped.
+    pub fn start(&self) -> SharedSpanGuard<'_, C> {
+        self.start_span(None)
+    }
+
+    /// Starts a new time span labelled `name`, which is stopped when the
+    /// returned guard is dropped.
+    pub fn start_named(&self, name: impl Into<Cow<'static, str>>) -> SharedSpanGuard<'_, C> {
+        self.start_span(Some(name.into()))
+    }
+
+    /// Adds a time span measured elsewhere. A running time span is stopped now.
+    ///
+    /// Fails if the time span stops before it starts.
+    pub fn add_span(&self, mut span: TimeSpan<C>) -> Result<(), SpanError> {
+        let stop = *span.stop.get_or_insert_with(|| self.clock.now());
+        if stop < span.start {
+            return Err(SpanError::StopBeforeStart);
+        }
+        let nanos = to_nanos(self.clock.duration_between(span.start, stop));
+        self.update(|| self.add_completed(nanos));
+        self.push_stopped(current_shard(), span);
+        Ok(())
+    }
+
+    /// Returns whether any time span is running.
+    pub fn is_running(&self) -> bool {
+        self.running_count.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns the total time accumulated by all time spans, including the
+    /// running ones.
+    ///
+    /// This does not wait for the threads which are starting or stopping
+    /// time spans.
+    pub fn elapsed(&self) -> Duration {
+        let (completed, count, start_sum) = loop {
+            let ended = self.updates_ended.load(Ordering::SeqCst);
+            let completed = self.completed_nanos.load(Ordering::SeqCst);
+            let count = self.running_count.load(Ordering::SeqCst);
+            let start_sum = self.running_start_nanos.load(Ordering::SeqCst);
+            // no update was in progress, nor began, while reading the counters.
+            if self.updates_begun.load(Ordering::SeqCst) == ended {
+                break (completed, count, start_sum);
+            }
+            std::hint::spin_loop();
+        };
+        // the sum of `now - start` over the running spans, which is exact as
+        // long as it fits in a `u64` even if `start_sum` wrapped.
+        let now = self.offset(self.clock.now());
+        let running = count.wrapping_mul(now).wrapping_sub(start_sum);
+        duration_from_nanos(completed as u128 + running as u128)
+    }
+
+    /// Returns a plain stopwatch containing the time spans kept by the history
+    /// limit, ordered by stop.
+    ///
+    /// Running time spans are included as if they were stopped now. Time
+    /// spans started or stopped while the snapshot is taken may be missing or
+    /// still running.
+    pub fn snapshot(&self) -> Stopwatch<C>
+    where
+        C: Clone,
+    {
+        let now = self.clock.now();
+        let mut spans = Vec::new();
+        let (mut evicted_count, mut evicted_elapsed) = (0, Duration::ZERO);
+        for shard in 0..SHARDS {
+            let shard = self.lock(shard);
+            spans.extend(shard.spans.iter().cloned());
+            spans.extend(shard.running.iter().flatten().map(|span| TimeSpan {
+                stop: Some(now.max(span.start)),
+                ..span.clone()
+            }));
+            evicted_count += shard.evicted_count;
+            evicted_elapsed = evicted_elapsed.saturating_add(shard.evicted_elapsed);
+        }
+        spans.sort_by_key(|span| span.stop);
+
+        // without time spans, this cannot fail.
+        let mut stopwatch = Stopwatch::from_parts(
+            self.clock.clone(),
+            Vec::new(),
+            evicted_count,
+            evicted_elapsed,
+        )
+        .unwrap();
+        stopwatch.set_history_limit(self.history_limit());
+        for span in spans {
+            stopwatch.record_stopped(span.start, span.stop.unwrap_or(now), span.label);
+        }
+        stopwatch
+    }
+
+    fn start_span(&self, label: Option<Cow<'static, str>>) -> SharedSpanGuard<'_, C> {
+        let start = self.clock.now();
+        let shard = current_shard();
+        let span = TimeSpan {
+            start,
+            stop: None,
+            label,
+        };
+        let mut state = self.lock(shard);
+        let slot = match state.free_slots.pop() {
+            Some(slot) => {
+                state.running[slot] = Some(span);
+                slot
+            }
+            None => {
+                state.running.push(Some(span));
+                state.running.len() - 1
+            }
+        };
+        drop(state);
+        let offset = self.offset(start);
+        self.update(|| {
+            self.running_count.fetch_add(1, Ordering::SeqCst);
+            self.running_start_nanos.fetch_add(offset, Ordering::SeqCst);
+        });
+        SharedSpanGuard {
+            stopwatch: self,
+            start,
+            shard,
+            slot,
+            stopped: false,
+        }
+    }
+
+    fn stop_span(&self, start: C::Instant, shard: usize, slot: usize) -> Duration {
+        let stop = self.clock.now().max(start);
+        let elapsed = self.clock.duration_between(start, stop);
+        let offset = self.offset(start);
+        self.update(|| {
+            self.running_count.fetch_sub(1, Ordering::SeqCst);
+            self.running_start_nanos.fetch_sub(offset, Ordering::SeqCst);
+            self.add_completed(to_nanos(elapsed));
+        });
+        let mut state = self.lock(shard);
+        let mut span = state.running[slot].take().unwrap();
+        state.free_slots.push(slot);
+        drop(state);
+        span.stop = Some(stop);
+        self.push_stopped(shard, span);
+        elapsed
+    }
+
+    fn push_stopped(&self, shard: usize, span: TimeSpan<C>) {
+        let limit = self.history_limit.load(Ordering::Relaxed);
+        let mut state = self.lock(shard);
+        state.spans.push_back(span);
+        state.evict(limit, &self.clock);
+    }
+
+    /// Runs `f`, which updates the counters read by `elapsed`.
+    fn update(&self, f: impl FnOnce()) {
+        self.updates_begun.fetch_add(1, Ordering::SeqCst);
+        f();
+        self.updates_ended.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn add_completed(&self, nanos: u64) {
+        let add = |completed: u64| Some(completed.saturating_add(nanos));
+        let _ = self
+            .completed_nanos
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, add);
+    }
+
+    fn lock(&self, shard: usize) -> MutexGuard<'_, Shard<C>> {
+        // a shard is never left inconsistent, so a poisoned lock can be used.
+        self.shards[shard].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn offset(&self, instant: C::Instant) -> u64 {
+        to_nanos(self.clock.duration_between(self.epoch, instant))
+    }
+}
+
+/// Returns the shard of the current thread, or the first one if the thread is
+/// exiting.
+fn current_shard() -> usize {
+    SHARD.try_with(|&shard| shard).unwrap_or(0)
+}
+
+/// A running time span of a `SharedStopwatch`, which is stopped when dropped.
+#[derive(Debug)]
+pub struct SharedSpanGuard<'a, C: Clock = StdClock> {
+    stopwatch: &'a SharedStopwatch<C>,
+    start: C::Instant,
+    shard: usize,
+    slot: usize,
+    stopped: bool,
+}
+
+impl<C: Clock> SharedSpanGuard<'_, C> {
+    /// Stops the time span and returns its duration.
+    pub fn stop(mut self) -> Duration {
+        self.stopped = true;
+        self.stopwatch.stop_span(self.start, self.shard, self.slot)
+    }
+}
+
+impl<C: Clock> Drop for SharedSpanGuard<'_, C> {
+    fn drop(&mut self) {
+        if !self.stopped {
+            self.stopwatch.stop_span(self.start, self.shard, self.slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn overlapping_spans() {
+        let clock = SharedManualClock::default();
+        let sw = SharedStopwatch::with_clock(clock.clone());
+        assert!(!sw.is_running());
+        let first = sw.start_named("first");
+        clock.advance(ms(10));
+        let second = sw.start();
+        assert!(sw.is_running());
+        clock.advance(ms(10));
+        assert_eq!(sw.elapsed(), ms(30));
+        assert_eq!(first.stop(), ms(20));
+        clock.advance(ms(10));
+        assert_eq!(sw.elapsed(), ms(40));
+
+        let snapshot = sw.snapshot();
+        assert_eq!(snapshot.spans().len(), 2);
+        assert_eq!(snapshot.spans()[0].label.as_deref(), Some("first"));
+        assert!(!snapshot.is_running());
+        assert_eq!(snapshot.elapsed(), ms(40));
+
+        drop(second);
+        assert!(!sw.is_running());
+        clock.advance(ms(10));
+        assert_eq!(sw.elapsed(), ms(40));
+    }
+
+    #[test]
+    fn add_span() {
+        let clock = SharedManualClock::default();
+        let sw = SharedStopwatch::with_clock(clock.clone());
+        let mut local = Stopwatch::with_clock(clock.clone());
+        local.start();
+        clock.advance(ms(10));
+        sw.add_span(local.spans()[0].clone()).unwrap();
+        assert_eq!(sw.elapsed(), ms(10));
+        assert_eq!(sw.snapshot().spans()[0].stop, Some(ms(10)));
+
+        let backwards = TimeSpan {
+            start: ms(10),
+            stop: Some(ms(5)),
+            label: None,
+        };
+        assert_eq!(sw.add_span(backwards), Err(SpanError::StopBeforeStart));
+        let future = TimeSpan {
+            start: ms(20),
+            stop: None,
+            label: None,
+        };
+        assert_eq!(sw.add_span(future), Err(SpanError::StopBeforeStart));
+        assert_eq!(sw.snapshot().spans().len(), 1);
+    }
+
+    #[test]
+    fn large_offsets() {
+        let clock = SharedManualClock::default();
+        let sw = SharedStopwatch::with_clock(clock.clone());
+        clock.advance(Duration::from_nanos(u64::MAX / 2 + 1));
+        // the sum of the start offsets overflows a u64.
+        let spans = [sw.start(), sw.start(), sw.start()];
+        clock.advance(ms(10));
+        assert_eq!(sw.elapsed(), ms(30));
+        drop(spans);
+        assert_eq!(sw.elapsed(), ms(30));
+
+        let long = TimeSpan {
+            start: Duration::ZERO,
+            stop: Some(Duration::from_nanos(u64::MAX)),
+            label: None,
+        };
+        sw.add_span(long.clone()).unwrap();
+        sw.add_span(long).unwrap();
+        assert_eq!(sw.elapsed(), Duration::from_nanos(u64::MAX));
+    }
+
+    #[test]
+    fn history_limit() {
+        let clock = SharedManualClock::default();
+        let sw = SharedStopwatch::with_clock(clock.clone());
+        assert_eq!(sw.history_limit(), Some(1000));
+        sw.set_history_limit(Some(2));
+        for i in 1..=3 {
+            let span = sw.start();
+            clock.advance(ms(i));
+            span.stop();
+        }
+        let running = sw.start();
+        clock.advance(ms(10));
+        assert_eq!(sw.elapsed(), ms(16));
+
+        let snapshot = sw.snapshot();
+        assert_eq!(snapshot.history_limit(), Some(2));
+        assert_eq!(snapshot.spans().len(), 2);
+        assert_eq!(snapshot.evicted_count(), 2);
+        assert_eq!(snapshot.evicted_elapsed(), ms(3));
+        assert_eq!(snapshot.elapsed(), ms(16));
+        drop(running);
+
+        sw.set_history_limit(None);
+        assert_eq!(sw.history_limit(), None);
+        sw.start();
+        assert_eq!(sw.snapshot().spans().len(), 3);
+    }
+
+    #[test]
+    fn many_threads() {
+        let clock = SharedManualClock::default();
+        let sw = SharedStopwatch::with_clock(clock.clone());
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let span = sw.start();
+                        clock.advance(ms(1));
+                        span.stop();
+                        sw.elapsed();
+                    }
+                });
+            }
+        });
+        assert!(!sw.is_running());
+        let snapshot = sw.snapshot();
+        assert_eq!(snapshot.spans().len(), 800);
+        assert_eq!(sw.elapsed(), snapshot.elapsed());
+    }
+}
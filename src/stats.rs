@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use crate::{duration_from_nanos, Clock, Stopwatch};
+
+/// Statistics over the durations of a set of time spans.
+///
+/// All values are zero when there are no time spans.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut s = Stopwatch::default();
+/// for _ in 0..10 {
+///     s.start();
+/// }
+/// s.stop();
+/// let stats = s.stats();
+/// assert_eq!(stats.count, 10);
+/// println!("mean: {:?}, p99: {:?}", stats.mean, stats.p99());
+/// ```
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct SpanStats {
+    /// The number of time spans.
+    pub count: usize,
+    /// The sum of all durations.
+    pub total: Duration,
+    /// The shortest duration.
+    pub min: Duration,
+    /// The longest duration.
+    pub max: Duration,
+    /// The arithmetic mean of the durations.
+    pub mean: Duration,
+    /// The median duration, which is the same as the 50th percentile.
+    pub median: Duration,
+    /// The population standard deviation of the durations.
+    pub std_dev: Duration,
+    sorted: Vec<Duration>,
+}
+
+impl SpanStats {
+    /// Computes the statistics of the given durations.
+    pub fn from_durations(durations: impl IntoIterator<Item = Duration>) -> Self {
+        let mut sorted: Vec<Duration> = durations.into_iter().collect();
+        if sorted.is_empty() {
+            return Self::default();
+        }
+        sorted.sort_unstable();
+
+        let count = sorted.len();
+        let total = sorted
+            .iter()
+            .fold(Duration::ZERO, |acc, d| acc.saturating_add(*d));
+        let mean = duration_from_nanos(total.as_nanos() / count as u128);
+        let variance = sorted
+            .iter()
+            .map(|d| {
+                let deviation = d.as_secs_f64() - mean.as_secs_f64();
+                deviation * deviation
+            })
+            .sum::<f64>()
+            / count as f64;
+
+        let mut stats = SpanStats {
+            count,
+            total,
+            min: sorted[0],
+            max: sorted[count - 1],
+            mean,
+            median: Duration::ZERO,
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+            sorted,
+        };
+        stats.median = stats.percentile(50.0);
+        stats
+    }
+
+    /// Returns the `p`th percentile of the durations, with `p` between 0 and
+    /// 100.
+    ///
+    /// Values of `p` outside of that range are clamped, and NaN is treated as
+    /// 0. Percentiles falling between two durations are linearly
+    /// interpolated.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let p = if p.is_nan() { 0.0 } else { p };
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (self.sorted.len() - 1) as f64;
+        let lower = self.sorted[rank.floor() as usize];
+        let upper = self.sorted[rank.ceil() as usize];
+        lower + (upper - lower).mul_f64(rank.fract())
+    }
+
+    /// Returns the 50th percentile of the durations.
+    pub fn p50(&self) -> Duration {
+        self.percentile(50.0)
+    }
+
+    /// Returns the 90th percentile of the durations.
+    pub fn p90(&self) -> Duration {
+        self.percentile(90.0)
+    }
+
+    /// Returns the 99th percentile of the durations.
+    pub fn p99(&self) -> Duration {
+        self.percentile(99.0)
+    }
+
+    /// Returns the durations, sorted from shortest to longest.
+    pub fn durations(&self) -> &[Duration] {
+        &self.sorted
+    }
+}
+
+impl<C: Clock> Stopwatch<C> {
+    /// Computes statistics over the durations of all time spans.
+    ///
+    /// A running time span is measured up to now.
+    pub fn stats(&self) -> SpanStats {
+        SpanStats::from_durations(self.iter().map(|s| s.elapsed_with(self.clock())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn empty() {
+        let stats = Stopwatch::default().stats();
+        assert_eq!(stats, SpanStats::default());
+        assert_eq!(stats.p99(), Duration::ZERO);
+    }
+
+    #[test]
+    fn from_durations() {
+        let stats = SpanStats::from_durations([ms(40), ms(10), ms(30), ms(20)]);
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.total, ms(100));
+        assert_eq!(stats.min, ms(10));
+        assert_eq!(stats.max, ms(40));
+        assert_eq!(stats.mean, ms(25));
+        assert_eq!(stats.median, ms(25));
+        assert_eq!(stats.percentile(0.0), ms(10));
+        assert_eq!(stats.percentile(100.0), ms(40));
+        assert_eq!(stats.percentile(150.0), ms(40));
+        assert_eq!(stats.percentile(f64::NAN), ms(10));
+        assert_eq!(stats.p90(), ms(37));
+        assert_eq!(stats.durations(), &[ms(10), ms(20), ms(30), ms(40)]);
+        let std_dev = stats.std_dev.as_secs_f64();
+        assert!((std_dev - 0.0111803).abs() < 1e-6, "{std_dev}");
+    }
+
+    #[test]
+    fn running_span() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start();
+        clock.advance(ms(10));
+        sw.start();
+        clock.advance(ms(30));
+        let stats = sw.stats();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.max, ms(30));
+        assert_eq!(stats.total, sw.elapsed());
+        clock.advance(ms(10));
+        assert_eq!(sw.stats().max, ms(40));
+    }
+}
@@ -0,0 +1,890 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::default::Default;
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Clock, ElapsedError, SpanError, StdClock, Transition};
+
+/// A span of time that is started but might not have an end yet.
+pub struct TimeSpan<C: Clock = StdClock> {
+    /// The instant at which the span started.
+    pub start: C::Instant,
+    /// The instant at which the span stopped, if any.
+    pub stop: Option<C::Instant>,
+    /// The name of what this span measured, if any.
+    pub label: Option<Cow<'static, str>>,
+}
+
+impl<C: Clock> TimeSpan<C> {
+    /// Returns the duration of this span, using `clock` to measure it if it
+    /// is still running.
+    pub fn elapsed_with(&self, clock: &C) -> Duration {
+        let stop = self.stop.unwrap_or_else(|| clock.now());
+        clock.duration_between(self.start, stop)
+    }
+
+    /// Returns the duration of this span like `elapsed_with`, or `None` if it
+    /// ends before it starts, which means the clock went backwards.
+    pub fn checked_elapsed_with(&self, clock: &C) -> Option<Duration> {
+        let stop = self.stop.unwrap_or_else(|| clock.now());
+        clock.checked_duration_between(self.start, stop)
+    }
+}
+
+impl<C: Clock> Clone for TimeSpan<C> {
+    fn clone(&self) -> Self {
+        TimeSpan {
+            start: self.start,
+            stop: self.stop,
+            label: self.label.clone(),
+        }
+    }
+}
+
+impl<C: Clock> fmt::Debug for TimeSpan<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimeSpan")
+            .field("start", &self.start)
+            .field("stop", &self.stop)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+/// Converts a TimeSpan into a Duration.
+impl From<TimeSpan> for Duration {
+    fn from(val: TimeSpan) -> Self {
+        val.elapsed_with(&StdClock)
+    }
+}
+
+/// A stopwatch used to calculate time differences.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut s = Stopwatch::default();
+/// s.start(); // Starts the stopwatch.
+/// s.start(); // Creates a new time span, which are commonly called "splits".
+/// s.stop(); // Stops the stopwatch.
+/// println!("{}", s); // Prints the total time.
+/// println!("{:?}", s); // Prints the different time spans as debug information.
+/// let total_time = s.elapsed(); // returns the total time as a Duration.
+/// for span in &s {
+///     println!("{:?} -> {:?}", span.start, span.stop);
+/// }
+/// s.reset(); // Reset the stopwatch.
+/// println!("{}", s); // Prints the total time.
+/// println!("{:?}", s); // Prints the different time spans as debug information.
+/// ```
+///
+/// A stopwatch measures time with a `Clock`, which is `StdClock` by default.
+/// Use `Stopwatch::with_clock` to measure time with another clock.
+///
+/// By default, a stopwatch keeps all of its time spans. Use
+/// `Stopwatch::set_history_limit` to only keep the most recent ones.
+#[derive(Clone)]
+pub struct Stopwatch<C: Clock = StdClock> {
+    // All the time spans that this stopwatch has been or is still running.
+    // Only the last timespan is allowed to have no stop value, which means it
+    // is still active. The others are ordered by stop.
+    // The first `head` spans were evicted by the history limit, they are
+    // removed in batches to keep evictions cheap.
+    spans: Vec<TimeSpan<C>>,
+    head: usize,
+    history_limit: Option<usize>,
+    evicted_count: usize,
+    evicted_elapsed: Duration,
+    pub(crate) clock: C,
+}
+
+impl<C: Clock + fmt::Debug> fmt::Debug for Stopwatch<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stopwatch")
+            .field("spans", &self.spans())
+            .field("history_limit", &self.history_limit)
+            .field("evicted_count", &self.evicted_count)
+            .field("evicted_elapsed", &self.evicted_elapsed)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Stopwatch::with_clock(StdClock)
+    }
+}
+
+impl<'a, C: Clock> IntoIterator for &'a Stopwatch<C> {
+    type Item = &'a TimeSpan<C>;
+    type IntoIter = std::slice::Iter<'a, TimeSpan<C>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Prints the total time this Stopwatch has run, in seconds.
+///
+/// A precision, as in `{:.3}`, sets the number of decimal digits. Use
+/// `Stopwatch::display_with` for other units and layouts.
+impl<C: Clock> fmt::Display for Stopwatch<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.elapsed().as_secs_f64();
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*}s", precision, secs),
+            None => write!(f, "{}s", secs),
+        }
+    }
+}
+
+impl<C: Clock> Stopwatch<C> {
+    /// Creates a stopped stopwatch which measures time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        Stopwatch {
+            spans: Vec::new(),
+            head: 0,
+            history_limit: None,
+            evicted_count: 0,
+            evicted_elapsed: Duration::ZERO,
+            clock,
+        }
+    }
+
+    /// Creates a stopped or running stopwatch from its time spans and the
+    /// time accumulated by evicted ones.
+    ///
+    /// Fails if a time span stops before it starts, if one which is not the
+    /// last is running, or if they are not ordered by stop.
+    pub(crate) fn from_parts(
+        clock: C,
+        spans: Vec<TimeSpan<C>>,
+        evicted_count: usize,
+        evicted_elapsed: Duration,
+    ) -> Result<Self, SpanError> {
+        for (index, span) in spans.iter().enumerate() {
+            match span.stop {
+                Some(stop) if stop < span.start => return Err(SpanError::StopBeforeStart),
+                None if index + 1 != spans.len() => return Err(SpanError::RunningNotLast),
+                _ => {}
+            }
+        }
+        if spans
+            .windows(2)
+            .any(|pair| pair[1].stop.is_some_and(|stop| pair[0].stop > Some(stop)))
+        {
+            return Err(SpanError::OutOfOrder);
+        }
+        Ok(Stopwatch {
+            spans,
+            evicted_count,
+            evicted_elapsed,
+            ..Stopwatch::with_clock(clock)
+        })
+    }
+
+    /// Returns the clock used by this stopwatch.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns all the time spans that this stopwatch has been or is still
+    /// running.
+    ///
+    /// Only the last time span may have no stop value, which means it is
+    /// still running. The others are ordered by stop, unless the clock went
+    /// backwards: their starts may be in any order, such as when time spans
+    /// measured concurrently overlap. Time spans evicted by the history limit
+    /// are not included.
+    pub fn spans(&self) -> &[TimeSpan<C>] {
+        &self.spans[self.head..]
+    }
+
+    /// Returns an iterator over the time spans.
+    pub fn iter(&self) -> std::slice::Iter<'_, TimeSpan<C>> {
+        self.spans().iter()
+    }
+
+    /// Stops the stopwatch and removes all its time spans, including the
+    /// time accumulated by evicted ones.
+    pub fn reset(&mut self) {
+        self.spans.clear();
+        self.head = 0;
+        self.evicted_count = 0;
+        self.evicted_elapsed = Duration::ZERO;
+    }
+
+    /// Keeps the first `len` time spans and removes the others.
+    pub fn truncate(&mut self, len: usize) {
+        self.compact();
+        self.spans.truncate(len);
+    }
+
+    /// Keeps only the time spans for which `f` returns `true`.
+    pub fn retain(&mut self, f: impl FnMut(&TimeSpan<C>) -> bool) {
+        self.compact();
+        self.spans.retain(f);
+    }
+
+    /// Returns the maximum number of time spans kept, if any.
+    pub fn history_limit(&self) -> Option<usize> {
+        self.history_limit
+    }
+
+    /// Sets the maximum number of time spans kept, or `None` to keep all of
+    /// them.
+    ///
+    /// Once the limit is reached, starting a time span evicts the oldest one.
+    /// Evicted time spans still count towards `elapsed`, but not towards
+    /// per-span queries such as labels or statistics. A limit of zero is
+    /// treated as one, as the running time span is always kept.
+    pub fn set_history_limit(&mut self, limit: Option<usize>) {
+        self.history_limit = limit.map(|limit| limit.max(1));
+        self.evict();
+    }
+
+    /// Returns the number of time spans evicted by the history limit.
+    pub fn evicted_count(&self) -> usize {
+        self.evicted_count
+    }
+
+    /// Returns the time accumulated by the time spans evicted by the history
+    /// limit.
+    pub fn evicted_elapsed(&self) -> Duration {
+        self.evicted_elapsed
+    }
+
+    /// Adds a time span after all the others.
+    ///
+    /// The time span may be running, in which case the stopwatch is running
+    /// afterwards. It fails like `insert_span`.
+    pub fn push_span(&mut self, span: TimeSpan<C>) -> Result<(), SpanError> {
+        self.insert_span(self.spans().len(), span)
+    }
+
+    /// Adds a stopped time span after the ones which stopped before it, and
+    /// before the running one if any.
+    pub(crate) fn push_stopped_span(&mut self, span: TimeSpan<C>) -> Result<(), SpanError> {
+        let stop = span.stop.ok_or(SpanError::RunningNotLast)?;
+        if stop < span.start {
+            return Err(SpanError::StopBeforeStart);
+        }
+        self.record_stopped(span.start, stop, span.label);
+        Ok(())
+    }
+
+    /// Adds a time span from `start` to `stop` like `push_stopped_span`,
+    /// stopping it at `start` if `stop` is before it.
+    pub(crate) fn record_stopped(
+        &mut self,
+        start: C::Instant,
+        stop: C::Instant,
+        label: Option<Cow<'static, str>>,
+    ) {
+        let stop = stop.max(start);
+        let spans = self.spans();
+        // usually last, unless another measurement stopped later but was
+        // recorded first.
+        let mut index = spans.len() - self.is_running() as usize;
+        while index > 0 && spans[index - 1].stop.is_some_and(|other| other > stop) {
+            index -= 1;
+        }
+        self.compact();
+        let span = TimeSpan {
+            start,
+            stop: Some(stop),
+            label,
+        };
+        self.spans.insert(index, span);
+        self.evict();
+    }
+
+    /// Inserts a time span at `index`, shifting the following ones.
+    ///
+    /// Fails if the time span stops before it starts, if it would break the
+    /// rule that only the last time span may be running, or if it would not
+    /// be ordered by stop with its neighbours.
+    pub fn insert_span(&mut self, index: usize, span: TimeSpan<C>) -> Result<(), SpanError> {
+        let len = self.spans().len();
+        if index > len {
+            return Err(SpanError::OutOfBounds { index, len });
+        }
+        if span.stop.is_some_and(|stop| stop < span.start) {
+            return Err(SpanError::StopBeforeStart);
+        }
+        let running_before = index == len && self.is_running();
+        if running_before || (span.stop.is_none() && index != len) {
+            return Err(SpanError::RunningNotLast);
+        }
+        if let Some(stop) = span.stop {
+            let spans = self.spans();
+            let after_previous = index == 0 || spans[index - 1].stop <= Some(stop);
+            let before_next = spans
+                .get(index)
+                .and_then(|next| next.stop)
+                .is_none_or(|next| stop <= next);
+            if !after_previous || !before_next {
+                return Err(SpanError::OutOfOrder);
+            }
+        }
+        self.compact();
+        self.spans.insert(index, span);
+        self.evict();
+        Ok(())
+    }
+
+    /// Evicts the oldest time spans beyond the history limit.
+    fn evict(&mut self) {
+        let Some(limit) = self.history_limit else {
+            return;
+        };
+        let excess = self.spans().len().saturating_sub(limit);
+        for span in &self.spans[self.head..self.head + excess] {
+            self.evicted_elapsed = self
+                .evicted_elapsed
+                .saturating_add(span.elapsed_with(&self.clock));
+        }
+        self.evicted_count += excess;
+        self.head += excess;
+        // removing evicted spans once there are as many as kept ones bounds
+        // the memory used while keeping evictions amortized constant time.
+        if self.head >= limit {
+            self.compact();
+        }
+    }
+
+    /// Removes the evicted time spans from memory.
+    fn compact(&mut self) {
+        self.spans.drain(..self.head);
+        self.head = 0;
+    }
+
+    /// Starts the stopwatch.
+    ///
+    /// If it is already started, it will create a new split.
+    /// This means it will stop and start the stopwatch, creating a new TimeSpan
+    /// in the process.
+    /// Use `resume` to start the stopwatch without creating a split.
+    pub fn start(&mut self) -> Option<TimeSpan<C>> {
+        // if no split or last split is stopped, create new one.
+        let ret = self.stop();
+        self.spans.push(TimeSpan {
+            start: self.clock.now(),
+            stop: None,
+            label: None,
+        });
+        self.evict();
+        ret
+    }
+
+    /// Starts the stopwatch like `start`, labelling the new time span with
+    /// `name`.
+    pub fn start_named(&mut self, name: impl Into<Cow<'static, str>>) -> Option<TimeSpan<C>> {
+        let ret = self.start();
+        self.spans.last_mut().unwrap().label = Some(name.into());
+        ret
+    }
+
+    /// Completes a lap: labels the running time span with `name` and starts a
+    /// new, unlabelled one.
+    ///
+    /// Returns the completed lap, or `None` if the stopwatch was not running,
+    /// in which case it is simply started: no lap was completed, so `name` is
+    /// not used and the new time span is unlabelled. Use `start_named` to
+    /// label the time span being started.
+    pub fn lap(&mut self, name: impl Into<Cow<'static, str>>) -> Option<TimeSpan<C>> {
+        if let Some(span) = self.spans.last_mut().filter(|s| s.stop.is_none()) {
+            span.label = Some(name.into());
+        }
+        self.start()
+    }
+
+    /// Stops the stopwatch without resetting it.
+    pub fn stop(&mut self) -> Option<TimeSpan<C>> {
+        let mut ret = None;
+        if self.is_running() {
+            self.spans.last_mut().unwrap().stop = Some(self.clock.now());
+            ret = Some(self.spans.last().unwrap().clone());
+        }
+        ret
+    }
+
+    /// Starts the stopwatch if it is stopped, otherwise does nothing.
+    ///
+    /// Unlike `start`, this never creates a new split.
+    pub fn resume(&mut self) -> Transition {
+        if self.is_running() {
+            Transition::Unchanged
+        } else {
+            self.start();
+            Transition::Started
+        }
+    }
+
+    /// Stops the stopwatch if it is running, otherwise does nothing.
+    pub fn pause(&mut self) -> Transition {
+        match self.stop() {
+            Some(_) => Transition::Stopped,
+            None => Transition::Unchanged,
+        }
+    }
+
+    /// Stops the stopwatch if it is running, otherwise starts it.
+    pub fn toggle(&mut self) -> Transition {
+        match self.stop() {
+            Some(_) => Transition::Stopped,
+            None => {
+                self.start();
+                Transition::Started
+            }
+        }
+    }
+
+    /// Creates a new split if the stopwatch is running, otherwise does
+    /// nothing.
+    ///
+    /// Unlike `start`, this never starts a stopped stopwatch.
+    pub fn split(&mut self) -> Transition {
+        if self.is_running() {
+            self.start();
+            Transition::Split
+        } else {
+            Transition::Unchanged
+        }
+    }
+
+    /// Returns whether the stopwatch is running.
+    pub fn is_running(&self) -> bool {
+        // if no spans or last span has an end, we are not running.
+        // equiv: if we have splits and the last one has no stop
+        !self.spans().is_empty() && self.spans().last().unwrap().stop.is_none()
+    }
+
+    /// Returns the total elapsed time accumulated inside of this stopwatch.
+    ///
+    /// This includes the time spans evicted by the history limit.
+    ///
+    /// This never panics: see `saturating_elapsed`. Use `try_elapsed` to
+    /// detect clock anomalies.
+    pub fn elapsed(&self) -> Duration {
+        self.saturating_elapsed()
+    }
+
+    /// Returns the total elapsed time, saturating at `Duration::MAX`.
+    ///
+    /// Time spans which end before they start count as zero.
+    pub fn saturating_elapsed(&self) -> Duration {
+        self.iter().fold(self.evicted_elapsed, |total, s| {
+            total.saturating_add(s.elapsed_with(&self.clock))
+        })
+    }
+
+    /// Returns the total elapsed time, or an error if a time span ends before
+    /// it starts or if the total overflows.
+    ///
+    /// Time spans evicted by the history limit are not checked again.
+    pub fn try_elapsed(&self) -> Result<Duration, ElapsedError> {
+        self.iter()
+            .enumerate()
+            .try_fold(self.evicted_elapsed, |total, (index, s)| {
+                let elapsed = s
+                    .checked_elapsed_with(&self.clock)
+                    .ok_or(ElapsedError::ClockWentBackwards { index })?;
+                total.checked_add(elapsed).ok_or(ElapsedError::Overflow)
+            })
+    }
+
+    /// Returns the first time span labelled `name`.
+    pub fn span_named(&self, name: &str) -> Option<&TimeSpan<C>> {
+        self.iter().find(|s| s.label.as_deref() == Some(name))
+    }
+
+    /// Returns the total time accumulated by the time spans labelled `name`,
+    /// saturating at `Duration::MAX`.
+    pub fn elapsed_named(&self, name: &str) -> Duration {
+        self.iter()
+            .filter(|s| s.label.as_deref() == Some(name))
+            .fold(Duration::ZERO, |total, s| {
+                total.saturating_add(s.elapsed_with(&self.clock))
+            })
+    }
+
+    /// Returns the total time accumulated for each label, ignoring unlabelled
+    /// time spans, saturating at `Duration::MAX`.
+    pub fn elapsed_by_label(&self) -> BTreeMap<Cow<'static, str>, Duration> {
+        let mut totals = BTreeMap::new();
+        for span in self {
+            if let Some(label) = &span.label {
+                let total = totals.entry(label.clone()).or_insert(Duration::ZERO);
+                *total = total.saturating_add(span.elapsed_with(&self.clock));
+            }
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+
+    static SLEEP_MS: u64 = 50;
+    static TOLERANCE_PERCENTAGE: f64 = 0.3;
+
+    #[test]
+    fn repeated_stops() {
+        let mut sw = Stopwatch::default();
+        for _ in 0..1000 {
+            sw.start();
+        }
+        sw.stop();
+        assert_eq!(sw.spans().len(), 1000);
+        assert!(sw.spans().last().unwrap().stop.is_some());
+    }
+
+    #[test]
+    fn elapsed_none() {
+        let mut sw = Stopwatch::default();
+        sw.stop();
+        sw.stop();
+        assert_eq!(sw.elapsed().as_secs_f32(), 0.0);
+    }
+
+    #[test]
+    fn elapsed_ms() {
+        let mut sw = Stopwatch::default();
+        sw.start();
+        sleep_ms(SLEEP_MS);
+        assert_duration_near(sw.elapsed(), SLEEP_MS);
+    }
+
+    #[test]
+    fn stop() {
+        let mut sw = Stopwatch::default();
+        sw.start();
+        sleep_ms(SLEEP_MS);
+        sw.stop();
+        assert_duration_near(sw.elapsed(), SLEEP_MS);
+        sleep_ms(SLEEP_MS);
+        assert_duration_near(sw.elapsed(), SLEEP_MS);
+    }
+
+    #[test]
+    fn resume_once() {
+        let mut sw = Stopwatch::default();
+        assert_eq!(sw.spans().len(), 0);
+        sw.start();
+        assert_eq!(sw.spans().len(), 1);
+        sleep_ms(SLEEP_MS);
+        sw.stop();
+        assert_eq!(sw.spans().len(), 1);
+        assert_duration_near(sw.elapsed(), SLEEP_MS);
+        sw.start();
+        assert_eq!(sw.spans().len(), 2);
+        sleep_ms(SLEEP_MS);
+        assert_duration_near(sw.elapsed(), 2 * SLEEP_MS);
+    }
+
+    #[test]
+    fn resume_twice() {
+        let mut sw = Stopwatch::default();
+        assert_eq!(sw.spans().len(), 0);
+        sw.start();
+        sleep_ms(SLEEP_MS);
+        sw.stop();
+        assert_eq!(sw.spans().len(), 1);
+        assert_duration_near(sw.elapsed(), SLEEP_MS);
+        sw.start();
+        assert_eq!(sw.spans().len(), 2);
+        sleep_ms(SLEEP_MS);
+        sw.start();
+        assert_eq!(sw.spans().len(), 3);
+        assert_duration_near(sw.elapsed(), 2 * SLEEP_MS);
+        sw.start();
+        assert_eq!(sw.spans().len(), 4);
+        sleep_ms(SLEEP_MS);
+        assert_duration_near(sw.elapsed(), 3 * SLEEP_MS);
+    }
+
+    #[test]
+    fn is_running() {
+        let mut sw = Stopwatch::default();
+        assert!(!sw.is_running());
+        sw.start();
+        assert!(sw.is_running());
+        sw.stop();
+        assert!(!sw.is_running());
+    }
+
+    #[test]
+    fn reset() {
+        let mut sw = Stopwatch::default();
+        sw.start();
+        sleep_ms(SLEEP_MS);
+        sw.reset();
+        assert!(!sw.is_running());
+        sw.start();
+        sleep_ms(SLEEP_MS);
+        assert_duration_near(sw.elapsed(), SLEEP_MS);
+    }
+
+    #[test]
+    fn custom_clock() {
+        let clock = StepClock::default();
+        let mut sw = Stopwatch::with_clock(&clock);
+        sw.start(); // t = 0
+        sw.stop(); // t = 1
+        assert_eq!(sw.elapsed(), Duration::from_secs(1));
+        sw.start(); // t = 2
+        assert_eq!(sw.elapsed(), Duration::from_secs(2)); // running span measured at t = 3
+    }
+
+    #[test]
+    fn named_spans() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start();
+        clock.advance(Duration::from_millis(10));
+        let lap = sw.lap("load").unwrap();
+        assert_eq!(lap.label.as_deref(), Some("load"));
+        clock.advance(Duration::from_millis(20));
+        sw.lap("parse");
+        clock.advance(Duration::from_millis(30));
+        sw.start_named("load");
+        clock.advance(Duration::from_millis(5));
+        sw.stop();
+
+        assert_eq!(sw.spans()[1].label.as_deref(), Some("parse"));
+        assert_eq!(sw.spans()[2].label, None);
+        assert_eq!(
+            sw.span_named("parse").unwrap().elapsed_with(&clock),
+            Duration::from_millis(20)
+        );
+        assert!(sw.span_named("missing").is_none());
+        assert_eq!(sw.elapsed_named("load"), Duration::from_millis(15));
+        let totals = sw.elapsed_by_label();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["load"], Duration::from_millis(15));
+        assert_eq!(totals["parse"], Duration::from_millis(20));
+    }
+
+    #[test]
+    fn lap_when_stopped() {
+        let mut sw = Stopwatch::default();
+        assert!(sw.lap("first").is_none());
+        assert!(sw.is_running());
+        // the name labels completed laps only.
+        assert_eq!(sw.spans()[0].label, None);
+    }
+
+    #[test]
+    fn transitions() {
+        let mut sw = Stopwatch::default();
+        assert_eq!(sw.pause(), Transition::Unchanged);
+        assert_eq!(sw.split(), Transition::Unchanged);
+        assert_eq!(sw.spans().len(), 0);
+        assert_eq!(sw.resume(), Transition::Started);
+        assert_eq!(sw.resume(), Transition::Unchanged);
+        assert_eq!(sw.spans().len(), 1);
+        assert_eq!(sw.split(), Transition::Split);
+        assert_eq!(sw.spans().len(), 2);
+        assert!(sw.is_running());
+        assert_eq!(sw.pause(), Transition::Stopped);
+        assert_eq!(sw.pause(), Transition::Unchanged);
+        assert_eq!(sw.toggle(), Transition::Started);
+        assert!(sw.is_running());
+        assert_eq!(sw.toggle(), Transition::Stopped);
+        assert!(!sw.is_running());
+        assert_eq!(sw.spans().len(), 3);
+    }
+
+    #[test]
+    fn span_insertion() {
+        let clock = SharedManualClock::default();
+        let at = |ms: u64| Duration::from_millis(ms);
+        let span = |start: u64, stop: Option<u64>| TimeSpan::<SharedManualClock> {
+            start: at(start),
+            stop: stop.map(at),
+            label: None,
+        };
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        assert_eq!(
+            sw.push_span(span(2, Some(1))),
+            Err(SpanError::StopBeforeStart)
+        );
+        assert_eq!(
+            sw.insert_span(1, span(0, None)),
+            Err(SpanError::OutOfBounds { index: 1, len: 0 })
+        );
+        sw.push_span(span(0, Some(10))).unwrap();
+        sw.push_span(span(20, None)).unwrap();
+        assert!(sw.is_running());
+        assert_eq!(
+            sw.push_span(span(30, Some(40))),
+            Err(SpanError::RunningNotLast)
+        );
+        assert_eq!(
+            sw.insert_span(0, span(0, None)),
+            Err(SpanError::RunningNotLast)
+        );
+        assert_eq!(
+            sw.insert_span(0, span(0, Some(11))),
+            Err(SpanError::OutOfOrder)
+        );
+        assert_eq!(
+            sw.insert_span(1, span(0, Some(5))),
+            Err(SpanError::OutOfOrder)
+        );
+        sw.insert_span(1, span(10, Some(15))).unwrap();
+        clock.advance(at(25));
+        assert_eq!(sw.elapsed(), at(20));
+        assert_eq!(sw.iter().count(), 3);
+
+        sw.retain(|s| s.stop.is_some());
+        assert!(!sw.is_running());
+        assert_eq!(sw.elapsed(), at(15));
+        sw.truncate(1);
+        assert_eq!(sw.elapsed(), at(10));
+        sw.reset();
+        assert!(sw.spans().is_empty());
+    }
+
+    #[test]
+    fn checked_elapsed() {
+        let clock = SetClock::default();
+        let mut sw = Stopwatch::with_clock(&clock);
+        clock.0.set(10);
+        sw.start();
+        clock.0.set(5);
+        sw.stop();
+        assert_eq!(sw.elapsed(), Duration::ZERO);
+        assert_eq!(
+            sw.try_elapsed(),
+            Err(ElapsedError::ClockWentBackwards { index: 0 })
+        );
+
+        sw.reset();
+        clock.0.set(0);
+        sw.start();
+        clock.0.set(u64::MAX);
+        sw.start();
+        assert_eq!(sw.try_elapsed(), Ok(Duration::from_secs(u64::MAX)));
+        clock.0.set(0);
+        assert_eq!(
+            sw.try_elapsed(),
+            Err(ElapsedError::ClockWentBackwards { index: 1 })
+        );
+        sw.reset();
+        sw.push_span(TimeSpan {
+            start: 0,
+            stop: Some(1),
+            label: None,
+        })
+        .unwrap();
+        sw.push_span(TimeSpan {
+            start: 0,
+            stop: Some(u64::MAX),
+            label: None,
+        })
+        .unwrap();
+        assert_eq!(sw.try_elapsed(), Err(ElapsedError::Overflow));
+        assert_eq!(sw.elapsed(), Duration::MAX);
+        assert_eq!(sw.saturating_elapsed(), Duration::MAX);
+
+        sw.reset();
+        for _ in 0..2 {
+            sw.push_span(TimeSpan {
+                start: 0,
+                stop: Some(u64::MAX),
+                label: Some("long".into()),
+            })
+            .unwrap();
+        }
+        assert_eq!(sw.elapsed_named("long"), Duration::MAX);
+        assert_eq!(sw.elapsed_by_label()["long"], Duration::MAX);
+    }
+
+    #[test]
+    fn history_limit() {
+        let clock = SharedManualClock::default();
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.set_history_limit(Some(3));
+        for i in 0..10 {
+            sw.start_named(format!("{}", i));
+            clock.advance(Duration::from_millis(1));
+        }
+        assert_eq!(sw.spans().len(), 3);
+        assert_eq!(sw.spans()[0].label.as_deref(), Some("7"));
+        assert!(sw.is_running());
+        assert_eq!(sw.evicted_count(), 7);
+        assert_eq!(sw.evicted_elapsed(), Duration::from_millis(7));
+        assert_eq!(sw.elapsed(), Duration::from_millis(10));
+        assert_eq!(sw.try_elapsed(), Ok(Duration::from_millis(10)));
+        assert!(sw.spans.len() <= 6);
+
+        sw.set_history_limit(Some(0));
+        assert_eq!(sw.spans().len(), 1);
+        assert!(sw.is_running());
+        assert_eq!(sw.elapsed(), Duration::from_millis(10));
+        sw.truncate(0);
+        assert!(!sw.is_running());
+        assert_eq!(sw.elapsed(), Duration::from_millis(9));
+        sw.reset();
+        assert_eq!(sw.evicted_count(), 0);
+        assert_eq!(sw.elapsed(), Duration::ZERO);
+        assert_eq!(sw.history_limit(), Some(1));
+    }
+
+    // helpers
+    /// A clock which advances by one second every time it is read.
+    #[derive(Default, Debug)]
+    struct StepClock(std::cell::Cell<u64>);
+
+    impl Clock for StepClock {
+        type Instant = u64;
+
+        fn now(&self) -> u64 {
+            let now = self.0.get();
+            self.0.set(now + 1);
+            now
+        }
+
+        fn duration_between(&self, earlier: u64, later: u64) -> Duration {
+            Duration::from_secs(later.saturating_sub(earlier))
+        }
+    }
+
+    /// A clock whose time in seconds is set by the test.
+    #[derive(Default, Debug)]
+    struct SetClock(std::cell::Cell<u64>);
+
+    impl Clock for SetClock {
+        type Instant = u64;
+
+        fn now(&self) -> u64 {
+            self.0.get()
+        }
+
+        fn duration_between(&self, earlier: u64, later: u64) -> Duration {
+            Duration::from_secs(later.saturating_sub(earlier))
+        }
+    }
+
+    fn sleep_ms(ms: u64) {
+        std::thread::sleep(Duration::from_millis(ms))
+    }
+
+    fn assert_near(x: i64, y: i64, tolerance: u64) {
+        let diff = (x - y).unsigned_abs();
+        if diff > tolerance {
+            panic!("Expected {x:?}, got {y:?}");
+        }
+    }
+
+    fn assert_duration_near(duration: Duration, elapsed: u64) {
+        let tolerance_value = (TOLERANCE_PERCENTAGE * elapsed as f64) as u64;
+        assert_near(elapsed as i64, duration.as_millis() as i64, tolerance_value);
+    }
+}
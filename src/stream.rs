@@ -0,0 +1,167 @@
+//! Instrumentation of streams, enabled by the `futures` feature.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+use crate::{Clock, SpanStats, StdClock, Stopwatch};
+
+pin_project! {
+    /// A stream measuring the time taken by each call to `poll_next` of another
+    /// one.
+    ///
+    /// Created by `StreamExt::timed`. Each poll is a time span of an internal
+    /// stopwatch, including the pending ones and the last one, which finds the
+    /// stream exhausted.
+    /// # Example
+    /// ```rust
+    /// use stopwatch2::*;
+    /// use futures_core::Stream;
+    /// use std::pin::pin;
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// # struct Items(u32);
+    /// # impl Stream for Items {
+    /// #     type Item = u32;
+    /// #     fn poll_next(mut self: std::pin::Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<u32>> {
+    /// #         self.0 += 1;
+    /// #         Poll::Ready(Some(self.0).filter(|&i| i <= 3))
+    /// #     }
+    /// # }
+    /// let mut items = pin!(Items(0).timed());
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// while let Poll::Ready(Some(_)) = items.as_mut().poll_next(&mut cx) {}
+    /// assert!(items.is_exhausted());
+    /// assert_eq!(items.stats().count, 4); // 3 items, and the end of the stream.
+    /// println!("total {:?}", items.elapsed());
+    /// ```
+    #[derive(Debug)]
+    #[must_use = "streams do nothing unless polled"]
+    pub struct TimedStream<S, C: Clock = StdClock> {
+        #[pin]
+        stream: S,
+        stopwatch: Stopwatch<C>,
+        exhausted: bool,
+    }
+}
+
+impl<S, C: Clock> TimedStream<S, C> {
+    /// Returns the stopwatch holding one time span per call to `poll_next`.
+    pub fn stopwatch(&self) -> &Stopwatch<C> {
+        &self.stopwatch
+    }
+
+    /// Returns statistics over the time taken by the calls to `poll_next`.
+    pub fn stats(&self) -> SpanStats {
+        self.stopwatch.stats()
+    }
+
+    /// Returns the total time spent in `poll_next`.
+    pub fn elapsed(&self) -> Duration {
+        self.stopwatch.elapsed()
+    }
+
+    /// Returns whether `poll_next` returned `Poll::Ready(None)`.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Returns the stopwatch holding one time span per call to `poll_next`.
+    pub fn into_stopwatch(self) -> Stopwatch<C> {
+        self.stopwatch
+    }
+}
+
+impl<S: Stream, C: Clock> Stream for TimedStream<S, C> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.project();
+        let poll = this.stopwatch.record(|| this.stream.poll_next(cx));
+        *this.exhausted |= matches!(poll, Poll::Ready(None));
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Adds `timed` to all streams.
+pub trait StreamExt: Stream + Sized {
+    /// Measures the time taken by each call to `poll_next` of this stream.
+    fn timed(self) -> TimedStream<Self> {
+        self.timed_with(StdClock)
+    }
+
+    /// Measures the time taken by each call to `poll_next` like `timed`,
+    /// using `clock`.
+    fn timed_with<C: Clock>(self, clock: C) -> TimedStream<Self, C> {
+        TimedStream {
+            stream: self,
+            stopwatch: Stopwatch::with_clock(clock),
+            exhausted: false,
+        }
+    }
+}
+
+impl<S: Stream> StreamExt for S {}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use futures_core::Stream;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Yields 1 to 3, pending before each item, advancing the clock by the
+    /// item in milliseconds.
+    struct Items {
+        clock: SharedManualClock,
+        next: u64,
+        pending: bool,
+    }
+
+    impl Stream for Items {
+        type Item = u64;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<u64>> {
+            self.pending = !self.pending;
+            if self.pending && self.next < 3 {
+                return Poll::Pending;
+            }
+            self.next += 1;
+            self.clock.advance(ms(self.next));
+            Poll::Ready(Some(self.next).filter(|&i| i <= 3))
+        }
+    }
+
+    #[test]
+    fn times_each_poll() {
+        let clock = SharedManualClock::default();
+        let items = Items {
+            clock: clock.clone(),
+            next: 0,
+            pending: false,
+        };
+        let mut stream = items.timed_with(clock.clone());
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut collected = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => collected.push(item),
+                Poll::Ready(None) => break,
+                Poll::Pending => assert!(!stream.is_exhausted()),
+            }
+        }
+        assert_eq!(collected, [1, 2, 3]);
+        assert!(stream.is_exhausted());
+        assert_eq!(stream.stats().count, 7);
+        assert_eq!(stream.elapsed(), ms(10));
+        assert_eq!(stream.into_stopwatch().spans().len(), 7);
+    }
+}
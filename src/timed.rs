@@ -0,0 +1,41 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::registry::thread_registry;
+use crate::{registry, Registry};
+
+pub use stopwatch2_macros::timed;
+
+/// Records a call of a function annotated with `#[timed]` when dropped.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct TimedGuard {
+    name: &'static str,
+    // the registry of the thread which created the guard, unless `global` or
+    // the thread is exiting.
+    thread_registry: Option<Arc<Registry>>,
+    global: bool,
+    start: Instant,
+}
+
+impl TimedGuard {
+    pub fn new(name: &'static str, global: bool) -> Self {
+        TimedGuard {
+            name,
+            thread_registry: if global { None } else { thread_registry() },
+            global,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for TimedGuard {
+    fn drop(&mut self) {
+        let stop = Instant::now();
+        if self.global {
+            registry().record_stopped(self.name, self.start, stop);
+        } else if let Some(registry) = &self.thread_registry {
+            registry.record_stopped(self.name, self.start, stop);
+        }
+    }
+}
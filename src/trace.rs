@@ -0,0 +1,274 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::{
+    write_json_str, Clock, ProfileNode, Profiler, Registry, StdClock, Stopwatch, TimeSpan,
+};
+
+/// A collection of time spans exported in the Trace Event Format, which can be
+/// opened by `chrome://tracing` or Perfetto.
+///
+/// Instants are converted to microseconds from an origin. Each time span is
+/// a complete event on a thread id chosen by the caller. Nested time spans on
+/// the same thread, such as the sections of a `Profiler`, are shown nested.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// let mut trace = Trace::default(); // The origin is now.
+/// let mut s = Stopwatch::default();
+/// s.start_named("load");
+/// s.lap("parse");
+/// s.stop();
+/// trace.set_thread_name(1, "main");
+/// trace.add_stopwatch("work", &s, 1); // Unlabelled spans are named "work".
+/// let json = trace.to_json(); // Or write it to a file with `write_json`.
+/// assert!(json.starts_with(r#"{"traceEvents":["#));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Trace<C: Clock = StdClock> {
+    clock: C,
+    origin: C::Instant,
+    pid: u32,
+    events: Vec<Event>,
+}
+
+#[derive(Clone, Debug)]
+enum Event {
+    Complete {
+        name: Cow<'static, str>,
+        tid: u64,
+        // in microseconds from the origin.
+        ts: f64,
+        dur: f64,
+    },
+    ThreadName {
+        tid: u64,
+        name: Cow<'static, str>,
+    },
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Trace::with_clock(StdClock)
+    }
+}
+
+impl<C: Clock> Trace<C> {
+    /// Creates an empty trace whose origin is the current instant of `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        let origin = clock.now();
+        Trace::with_origin(clock, origin)
+    }
+
+    /// Creates an empty trace whose timestamps are offsets from `origin`.
+    ///
+    /// Time spans starting before the origin have negative timestamps.
+    pub fn with_origin(clock: C, origin: C::Instant) -> Self {
+        Trace {
+            clock,
+            origin,
+            pid: std::process::id(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns the instant from which timestamps are measured.
+    pub fn origin(&self) -> C::Instant {
+        self.origin
+    }
+
+    /// Sets the process id of the events, the current one by default.
+    pub fn set_pid(&mut self, pid: u32) {
+        self.pid = pid;
+    }
+
+    /// Names the thread `tid` in the viewer.
+    pub fn set_thread_name(&mut self, tid: u64, name: impl Into<Cow<'static, str>>) {
+        self.events.push(Event::ThreadName {
+            tid,
+            name: name.into(),
+        });
+    }
+
+    /// Adds a time span on the thread `tid`, named by its label or by `name`.
+    ///
+    /// A running time span ends now.
+    pub fn add_span(&mut self, name: impl Into<Cow<'static, str>>, span: &TimeSpan<C>, tid: u64) {
+        let name = span.label.clone().unwrap_or_else(|| name.into());
+        let ts = self.offset_us(span.start);
+        let dur = micros(span.elapsed_with(&self.clock));
+        self.events.push(Event::Complete { name, tid, ts, dur });
+    }
+
+    /// Adds the time spans of `stopwatch` on the thread `tid`, named by their
+    /// label or by `name`.
+    ///
+    /// Time spans evicted by the history limit are not included.
+    pub fn add_stopwatch(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        stopwatch: &Stopwatch<C>,
+        tid: u64,
+    ) {
+        let name = name.into();
+        for span in stopwatch {
+            self.add_span(name.clone(), span, tid);
+        }
+    }
+
+    /// Adds every call of every section of `profiler` on the thread `tid`.
+    pub fn add_profiler(&mut self, profiler: &Profiler<C>, tid: u64) {
+        fn add_node<C: Clock>(trace: &mut Trace<C>, node: ProfileNode<'_, C>, tid: u64) {
+            trace.add_stopwatch(node.name().to_owned(), node.stopwatch(), tid);
+            for child in node.children() {
+                add_node(trace, child, tid);
+            }
+        }
+
+        for root in profiler.roots() {
+            add_node(self, root, tid);
+        }
+    }
+
+    /// Adds the time spans of every stopwatch of `registry` on the thread
+    /// `tid`, named by their label or by the name of their stopwatch.
+    pub fn add_registry(&mut self, registry: &Registry<C>, tid: u64)
+    where
+        C: Clone,
+    {
+        for (name, stopwatch) in registry.snapshot() {
+            self.add_stopwatch(name, &stopwatch, tid);
+        }
+    }
+
+    /// Returns the number of events of the trace.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns whether the trace has no events.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns the trace as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.fmt_json(&mut json).unwrap();
+        json
+    }
+
+    /// Writes the trace as a JSON object to `writer`.
+    pub fn write_json(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(self.to_json().as_bytes())
+    }
+
+    fn fmt_json(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str(r#"{"traceEvents":["#)?;
+        for (i, event) in self.events.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match event {
+                Event::Complete { name, tid, ts, dur } => {
+                    f.write_str(r#"{"name":"#)?;
+                    write_json_str(f, name)?;
+                    write!(
+                        f,
+                        r#","ph":"X","ts":{:.3},"dur":{:.3},"pid":{},"tid":{}}}"#,
+                        ts, dur, self.pid, tid
+                    )?;
+                }
+                Event::ThreadName { tid, name } => {
+                    write!(
+                        f,
+                        r#"{{"name":"thread_name","ph":"M","pid":{},"tid":{},"args":{{"name":"#,
+                        self.pid, tid
+                    )?;
+                    write_json_str(f, name)?;
+                    f.write_str("}}")?;
+                }
+            }
+        }
+        f.write_str(r#"],"displayTimeUnit":"ms"}"#)
+    }
+
+    fn offset_us(&self, instant: C::Instant) -> f64 {
+        if instant >= self.origin {
+            micros(self.clock.duration_between(self.origin, instant))
+        } else {
+            -micros(self.clock.duration_between(instant, self.origin))
+        }
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn stopwatch_events() {
+        let clock = SharedManualClock::default();
+        clock.advance(ms(1));
+        let mut trace = Trace::with_clock(clock.clone());
+        trace.set_pid(7);
+        trace.set_thread_name(1, "main \"thread\"");
+
+        let mut sw = Stopwatch::with_clock(clock.clone());
+        sw.start_named("load");
+        clock.advance(Duration::from_micros(1500));
+        sw.start();
+        clock.advance(ms(2));
+        trace.add_stopwatch("work", &sw, 1);
+        trace.add_span(
+            "early",
+            &TimeSpan {
+                start: Duration::ZERO,
+                stop: Some(ms(1)),
+                label: None,
+            },
+            2,
+        );
+        assert_eq!(trace.len(), 4);
+        assert_eq!(
+            trace.to_json(),
+            concat!(
+                r#"{"traceEvents":["#,
+                r#"{"name":"thread_name","ph":"M","pid":7,"tid":1,"args":{"name":"main \"thread\""}},"#,
+                r#"{"name":"load","ph":"X","ts":0.000,"dur":1500.000,"pid":7,"tid":1},"#,
+                r#"{"name":"work","ph":"X","ts":1500.000,"dur":2000.000,"pid":7,"tid":1},"#,
+                r#"{"name":"early","ph":"X","ts":-1000.000,"dur":1000.000,"pid":7,"tid":2}"#,
+                r#"],"displayTimeUnit":"ms"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn profiler_events() {
+        let clock = SharedManualClock::default();
+        let mut trace = Trace::with_clock(clock.clone());
+        let mut p = Profiler::with_clock(clock.clone());
+        p.enter("outer");
+        p.enter("inner");
+        clock.advance(ms(1));
+        p.exit();
+        p.exit();
+        trace.add_profiler(&p, 3);
+        let json = trace.to_json();
+        assert!(json.contains(r#"{"name":"outer","ph":"X","ts":0.000,"dur":1000.000"#));
+        assert!(json.contains(r#"{"name":"inner","ph":"X","ts":0.000,"dur":1000.000"#));
+
+        let mut out = Vec::new();
+        trace.write_json(&mut out).unwrap();
+        assert_eq!(out, json.as_bytes());
+    }
+}
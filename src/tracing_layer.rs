@@ -0,0 +1,275 @@
+//! Integration with the `tracing` crate, enabled by the `tracing` feature.
+
+use std::sync::Arc;
+
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::registry::DEFAULT_HISTORY_LIMIT;
+use crate::{Clock, Registry, StdClock, Stopwatch, TimeSpan};
+
+/// A `tracing_subscriber::Layer` measuring the time spent in `tracing` spans.
+///
+/// The busy time, while a span is entered, and the idle time, between its
+/// creation, entries and closing, are added to stopwatches named after the
+/// span, in two `Registry`s shared by the clones of the layer. Their
+/// stopwatches keep the last 1000 time spans, and older ones only count
+/// towards the total, see `Registry::set_history_limit`.
+///
+/// Each entry and exit of a span locks one of the registries, which are
+/// shared by all threads: spans entered very often from many threads at once
+/// contend on these locks.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+/// use tracing_subscriber::prelude::*;
+///
+/// let layer = StopwatchLayer::default();
+/// let subscriber = tracing_subscriber::registry().with(layer.clone());
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("request");
+///     let _entered = span.enter();
+///     // Handle the request...
+/// });
+/// println!("{:?}", layer.busy().elapsed("request")); // Time spent inside "request".
+/// println!("{:?}", layer.idle().elapsed("request")); // Time spent outside of it.
+/// ```
+#[derive(Debug)]
+pub struct StopwatchLayer<C: Clock = StdClock> {
+    busy: Arc<Registry<C>>,
+    idle: Arc<Registry<C>>,
+}
+
+impl<C: Clock> Clone for StopwatchLayer<C> {
+    fn clone(&self) -> Self {
+        StopwatchLayer {
+            busy: self.busy.clone(),
+            idle: self.idle.clone(),
+        }
+    }
+}
+
+impl Default for StopwatchLayer {
+    fn default() -> Self {
+        StopwatchLayer::with_clock(StdClock)
+    }
+}
+
+impl<C: Clock + Clone> StopwatchLayer<C> {
+    /// Creates a layer which measures time using `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        let busy = Registry::with_clock(clock.clone());
+        let idle = Registry::with_clock(clock);
+        busy.set_history_limit(Some(DEFAULT_HISTORY_LIMIT));
+        idle.set_history_limit(Some(DEFAULT_HISTORY_LIMIT));
+        StopwatchLayer {
+            busy: Arc::new(busy),
+            idle: Arc::new(idle),
+        }
+    }
+
+    /// Returns the stopwatches measuring the time spent inside of spans, by
+    /// span name.
+    pub fn busy(&self) -> &Registry<C> {
+        &self.busy
+    }
+
+    /// Returns the stopwatches measuring the time spent outside of spans while
+    /// they are open, by span name.
+    pub fn idle(&self) -> &Registry<C> {
+        &self.idle
+    }
+}
+
+/// The timing of a `tracing` span, stored in its extensions.
+struct Timing<I> {
+    // the number of times the span is currently entered.
+    depth: usize,
+    busy_since: Option<I>,
+    idle_since: Option<I>,
+}
+
+impl<S, C> Layer<S> for StopwatchLayer<C>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    C: Clock + Clone + Send + Sync + 'static,
+    C::Instant: Send + Sync,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        span.extensions_mut().insert(Timing {
+            depth: 0,
+            busy_since: None,
+            idle_since: Some(self.busy.clock().now()),
+        });
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(timing) = extensions.get_mut::<Timing<C::Instant>>() else {
+            return;
+        };
+        timing.depth += 1;
+        if timing.depth == 1 {
+            let now = self.busy.clock().now();
+            if let Some(idle_since) = timing.idle_since.take() {
+                self.idle.record_stopped(span.name(), idle_since, now);
+            }
+            timing.busy_since = Some(now);
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(timing) = extensions.get_mut::<Timing<C::Instant>>() else {
+            return;
+        };
+        timing.depth = timing.depth.saturating_sub(1);
+        if timing.depth == 0 {
+            let now = self.busy.clock().now();
+            if let Some(busy_since) = timing.busy_since.take() {
+                self.busy.record_stopped(span.name(), busy_since, now);
+            }
+            timing.idle_since = Some(now);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(timing) = extensions.get::<Timing<C::Instant>>() else {
+            return;
+        };
+        if let Some(idle_since) = timing.idle_since {
+            self.idle
+                .record_stopped(span.name(), idle_since, self.idle.clock().now());
+        }
+    }
+}
+
+impl<C: Clock> Stopwatch<C> {
+    /// Stops the stopwatch like `stop`, and emits a `tracing` event with the
+    /// elapsed time of the stopped time span, if any.
+    ///
+    /// The event has the target `stopwatch2`, the `INFO` level, and the
+    /// fields `stopwatch` set to `name`, `label` and `elapsed`.
+    pub fn stop_traced(&mut self, name: &str) -> Option<TimeSpan<C>> {
+        let span = self.stop()?;
+        self.trace_span(name, &span);
+        Some(span)
+    }
+
+    /// Starts the stopwatch like `start`, and emits a `tracing` event for the
+    /// time span which was stopped, if any, see `stop_traced`.
+    pub fn start_traced(&mut self, name: &str) -> Option<TimeSpan<C>> {
+        let span = self.start()?;
+        self.trace_span(name, &span);
+        Some(span)
+    }
+
+    fn trace_span(&self, name: &str, span: &TimeSpan<C>) {
+        tracing::info!(
+            target: "stopwatch2",
+            stopwatch = name,
+            label = span.label.as_deref(),
+            elapsed = ?span.elapsed_with(self.clock()),
+            "stopwatch stopped"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ms;
+    use crate::*;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, Layer};
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn busy_and_idle() {
+        let clock = SharedManualClock::default();
+        let layer = StopwatchLayer::with_clock(clock.clone());
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("work");
+            clock.advance(ms(5));
+            {
+                let _entered = span.enter();
+                clock.advance(ms(10));
+                let _reentered = span.enter();
+                clock.advance(ms(1));
+            }
+            clock.advance(ms(3));
+            span.in_scope(|| clock.advance(ms(2)));
+            clock.advance(ms(4));
+        });
+        let busy = layer.busy().get("work").unwrap();
+        assert_eq!(busy.history_limit(), Some(1000));
+        assert_eq!(busy.spans().len(), 2);
+        assert_eq!(busy.elapsed(), ms(13));
+        assert_eq!(layer.idle().elapsed("work"), Some(ms(12)));
+    }
+
+    #[derive(Default)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Events {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            struct Fields(String);
+
+            impl Visit for Fields {
+                fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                    self.0 += &format!("{}={:?} ", field.name(), value);
+                }
+            }
+
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            let mut events = self.0.lock().unwrap();
+            events.push(format!(
+                "{} {}",
+                event.metadata().target(),
+                fields.0.trim_end()
+            ));
+        }
+    }
+
+    #[test]
+    fn traced_stops() {
+        let clock = SharedManualClock::default();
+        let events = Events::default();
+        let recorded = events.0.clone();
+        let subscriber = tracing_subscriber::registry().with(events);
+        tracing::subscriber::with_default(subscriber, || {
+            let mut sw = Stopwatch::with_clock(clock.clone());
+            assert!(sw.stop_traced("db").is_none());
+            sw.start_named("query");
+            clock.advance(ms(10));
+            assert!(sw.start_traced("db").is_some());
+            clock.advance(ms(5));
+            assert!(sw.stop_traced("db").is_some());
+        });
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            [
+                "stopwatch2 message=stopwatch stopped stopwatch=\"db\" label=\"query\" elapsed=10ms",
+                "stopwatch2 message=stopwatch stopped stopwatch=\"db\" elapsed=5ms",
+            ]
+        );
+    }
+}
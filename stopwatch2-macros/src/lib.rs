@@ -0,0 +1,71 @@
+//! Procedural macros for the `stopwatch2` crate.
+//!
+//! They are re-exported by `stopwatch2` when its `macros` feature is enabled,
+//! and should be used through it.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr};
+
+/// Records the duration of each call of a function into a named stopwatch.
+///
+/// The stopwatch is named after the function, or after `name` if given. It
+/// belongs to the registry of the calling thread, see
+/// `stopwatch2::with_thread_registry`, or to the global registry, see
+/// `stopwatch2::registry`, with the `registry` argument.
+///
+/// The call is recorded however the function returns, including through `?`
+/// or a panic. For an `async fn`, the time is measured from the first poll of
+/// its future to its completion, and the stopwatch belongs to the registry of
+/// the thread which first polled it, even if another thread completes it.
+///
+/// The registries keep the last 1000 calls of each function, and older calls
+/// only count towards the total time.
+/// # Example
+/// ```rust
+/// use stopwatch2::*;
+///
+/// #[timed]
+/// fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+///     input.parse()
+/// }
+///
+/// #[timed(name = "db", registry)]
+/// async fn query() {}
+///
+/// parse("42").unwrap();
+/// with_thread_registry(|r| println!("{:?}", r.elapsed("parse")));
+/// ```
+#[proc_macro_attribute]
+pub fn timed(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name = None;
+    let mut global = false;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else if meta.path.is_ident("registry") {
+            global = true;
+            Ok(())
+        } else {
+            Err(meta.error("expected `name = \"...\"` or `registry`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+    let name = name.unwrap_or_else(|| LitStr::new(&sig.ident.to_string(), sig.ident.span()));
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            let __stopwatch2_timed = ::stopwatch2::TimedGuard::new(#name, #global);
+            #block
+        }
+    }
+    .into()
+}
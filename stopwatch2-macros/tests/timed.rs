@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use stopwatch2::*;
+
+fn calls(name: &str) -> usize {
+    with_thread_registry(|r| r.get(name).map_or(0, |s| s.spans().len()))
+}
+
+/// Polls `future` to completion on the current thread.
+///
+/// The test helpers of `stopwatch2` are private to its unit tests, so this
+/// one is kept here rather than exposed in its public API.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[timed]
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+#[timed(name = "parsing")]
+fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+    let value = input.parse::<u32>()?;
+    Ok(value * 2)
+}
+
+#[test]
+fn sync_functions() {
+    assert_eq!(add(1, 2), 3);
+    assert_eq!(add(3, 4), 7);
+    assert_eq!(calls("add"), 2);
+
+    assert_eq!(parse("21"), Ok(42));
+    assert!(parse("x").is_err());
+    assert_eq!(calls("parsing"), 2);
+    assert_eq!(calls("parse"), 0);
+}
+
+struct Counter(u32);
+
+impl Counter {
+    #[timed]
+    fn increment(&mut self) -> &mut Self {
+        self.0 += 1;
+        self
+    }
+}
+
+#[test]
+fn methods() {
+    let mut counter = Counter(0);
+    counter.increment().increment();
+    assert_eq!(counter.0, 2);
+    assert_eq!(calls("increment"), 2);
+}
+
+#[timed(registry, name = "timed_async")]
+async fn double(value: u32) -> u32 {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await;
+    value * 2
+}
+
+#[test]
+fn async_functions() {
+    let future = double(21);
+    assert!(registry().get("timed_async").is_none());
+    assert_eq!(block_on(future), 42);
+    let stopwatch = registry().get("timed_async").unwrap();
+    assert_eq!(stopwatch.spans().len(), 1);
+    assert_eq!(calls("timed_async"), 0);
+}
+
+#[timed(name = "timed_moved")]
+async fn yield_once() {
+    let mut yielded = false;
+    std::future::poll_fn(|_| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[test]
+fn async_functions_across_threads() {
+    let mut future = Box::pin(yield_once());
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    std::thread::spawn(move || {
+        block_on(future);
+        assert_eq!(calls("timed_moved"), 0);
+    })
+    .join()
+    .unwrap();
+    // recorded by the thread which started the call.
+    assert_eq!(calls("timed_moved"), 1);
+}
+
+struct OnExit;
+
+impl Drop for OnExit {
+    fn drop(&mut self) {
+        add(1, 1);
+    }
+}
+
+#[test]
+fn thread_exit() {
+    thread_local! {
+        static ON_EXIT: OnExit = const { OnExit };
+    }
+    std::thread::spawn(|| {
+        ON_EXIT.with(|_| {});
+        add(1, 1);
+    })
+    .join()
+    .unwrap();
+}